@@ -0,0 +1,120 @@
+// Filters the documents of the prebuilt SEARCH_INDEX (search-index.js) in the browser.
+// Documents are stored as [year, report type, language, link, keywords].
+(function () {
+    "use strict";
+
+    var index = window.SEARCH_INDEX;
+    var form = document.getElementById("search");
+    var results = document.getElementById("results");
+    var summary = document.getElementById("summary");
+    var MAX_RESULTS = 500;
+
+    function addOptions(select, values, label) {
+        values.forEach(function (value) {
+            var option = document.createElement("option");
+            option.value = value;
+            option.textContent = label ? label(value) : value;
+            select.appendChild(option);
+        });
+    }
+
+    function unique(values) {
+        return values.filter(function (value, i) {
+            return values.indexOf(value) === i;
+        }).sort();
+    }
+
+    var tags = [];
+    var years = [];
+    index.companies.forEach(function (company) {
+        tags = tags.concat(company.tags);
+        company.documents.forEach(function (d) {
+            years.push(d[0]);
+        });
+    });
+    years = unique(years).reverse();
+    addOptions(form.tag, unique(tags));
+    addOptions(form.from_year, years);
+    addOptions(form.to_year, years);
+    addOptions(form.report_type, Object.keys(index.types), function (code) {
+        return index.types[code];
+    });
+    addOptions(form.language, Object.keys(index.languages), function (code) {
+        return index.languages[code];
+    });
+
+    function cell(row, content) {
+        var td = document.createElement("td");
+        if (content instanceof Node) {
+            td.appendChild(content);
+        } else {
+            td.textContent = content;
+        }
+        row.appendChild(td);
+    }
+
+    function link(href, text) {
+        var a = document.createElement("a");
+        a.href = href;
+        a.textContent = text;
+        return a;
+    }
+
+    // Every word of the query has to appear in the company name, tags or keywords
+    function matchesQuery(words, company, keywords) {
+        var text = [company.name, company.country].concat(company.tags, keywords).join(" ").toLowerCase();
+        return words.every(function (word) {
+            return text.indexOf(word) !== -1;
+        });
+    }
+
+    function search() {
+        var words = form.query.value.toLowerCase().split(/\s+/).filter(Boolean);
+        var tag = form.tag.value;
+        var fromYear = parseInt(form.from_year.value, 10) || 0;
+        var toYear = parseInt(form.to_year.value, 10) || 9999;
+        var reportType = form.report_type.value;
+        var language = form.language.value;
+
+        var matches = [];
+        index.companies.forEach(function (company) {
+            if (tag && company.tags.indexOf(tag) === -1) {
+                return;
+            }
+            company.documents.forEach(function (d) {
+                if (d[0] < fromYear || d[0] > toYear) {
+                    return;
+                }
+                if ((reportType && d[1] !== reportType) || (language && d[2] !== language)) {
+                    return;
+                }
+                if (matchesQuery(words, company, d[4])) {
+                    matches.push([company, d]);
+                }
+            });
+        });
+
+        results.textContent = "";
+        matches.slice(0, MAX_RESULTS).forEach(function (match) {
+            var company = match[0];
+            var d = match[1];
+            var row = document.createElement("tr");
+            cell(row, link("companies/" + encodeURIComponent(company.name) + ".html", company.name));
+            cell(row, d[0]);
+            cell(row, link(d[3], index.types[d[1]] || d[1]));
+            cell(row, index.languages[d[2]] || d[2]);
+            cell(row, company.tags.join(", "));
+            results.appendChild(row);
+        });
+        summary.textContent = matches.length > MAX_RESULTS
+            ? matches.length + " documents found, showing the first " + MAX_RESULTS
+            : matches.length + " documents found";
+    }
+
+    form.addEventListener("input", search);
+    form.addEventListener("submit", function (event) {
+        event.preventDefault();
+        search();
+    });
+    search();
+})();
@@ -0,0 +1,75 @@
+use std::env;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+// Generates the ReportType enum from data/DocumentType.csv
+fn main() {
+    let source = "data/DocumentType.csv";
+    println!("cargo:rerun-if-changed={}", source);
+    let contents = fs::read_to_string(source).expect("Reading data/DocumentType.csv failed");
+
+    let mut types = Vec::new();
+    for (index, line) in contents.lines().enumerate().skip(1) {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let columns: Vec<&str> = line.split(';').map(|c| c.trim()).collect();
+        if columns.len() != 6 {
+            panic!("{}:{}: expected 6 columns", source, index + 1);
+        }
+        types.push(columns);
+    }
+
+    let mut code = String::new();
+    writeln!(
+        code,
+        "#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]"
+    )
+    .unwrap();
+    writeln!(code, "pub enum ReportType {{").unwrap();
+    for t in &types {
+        writeln!(code, "    {},", t[1]).unwrap();
+    }
+    writeln!(code, "    Other(String),").unwrap();
+    writeln!(code, "}}\n").unwrap();
+
+    writeln!(code, "impl ReportType {{").unwrap();
+    writeln!(code, "    pub const ALL: &'static [ReportType] = &[").unwrap();
+    for t in &types {
+        writeln!(code, "        ReportType::{},", t[1]).unwrap();
+    }
+    writeln!(code, "    ];\n").unwrap();
+
+    writeln!(code, "    pub fn from_code(code: &str) -> ReportType {{").unwrap();
+    writeln!(code, "        match code.to_ascii_uppercase().as_str() {{").unwrap();
+    for t in &types {
+        writeln!(code, "            {:?} => ReportType::{},", t[0], t[1]).unwrap();
+    }
+    writeln!(code, "            _ => ReportType::Other(code.to_owned()),").unwrap();
+    writeln!(code, "        }}\n    }}\n").unwrap();
+
+    writeln!(code, "    pub fn code(&self) -> &str {{").unwrap();
+    writeln!(code, "        match self {{").unwrap();
+    for t in &types {
+        writeln!(code, "            ReportType::{} => {:?},", t[1], t[0]).unwrap();
+    }
+    writeln!(code, "            ReportType::Other(code) => code,").unwrap();
+    writeln!(code, "        }}\n    }}\n").unwrap();
+
+    writeln!(code, "    fn names(&self) -> Option<[&'static str; 4]> {{").unwrap();
+    writeln!(code, "        match self {{").unwrap();
+    for t in &types {
+        writeln!(
+            code,
+            "            ReportType::{} => Some([{:?}, {:?}, {:?}, {:?}]),",
+            t[1], t[2], t[3], t[4], t[5]
+        )
+        .unwrap();
+    }
+    writeln!(code, "            ReportType::Other(_) => None,").unwrap();
+    writeln!(code, "        }}\n    }}\n}}").unwrap();
+
+    let destination = Path::new(&env::var("OUT_DIR").unwrap()).join("report_type.rs");
+    fs::write(destination, code).unwrap();
+}
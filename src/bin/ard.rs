@@ -0,0 +1,789 @@
+use chrono::{Datelike, NaiveDate, Utc};
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::process;
+use structopt::StructOpt;
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use log::{debug, error};
+use simplelog::*;
+
+use walkdir::WalkDir;
+
+use annual_report_database::checks::Nameserver;
+use annual_report_database::data_structures::*;
+use annual_report_database::diff::*;
+use annual_report_database::downloader::{Downloader, HttpStatusError};
+use annual_report_database::extraction::{ExtractionJob, ExtractionManifest, Extractor};
+use annual_report_database::key_figures::KeyFigureExtractor;
+use annual_report_database::output::OutputFormat;
+use annual_report_database::search::{IndexBuilder, SearchIndex};
+use annual_report_database::*;
+
+pub fn create_file_list(
+    path: &str,
+    filetype_filter_function: &dyn Fn(&str) -> bool,
+) -> Vec<PathBuf> {
+    let mut file_list = Vec::new();
+    let walker = WalkDir::new(path).into_iter();
+
+    for entry in walker.filter_map(|e| e.ok()) {
+        let path = entry.into_path();
+        if let Some(os_ext) = path.extension() {
+            if let Some(ext) = os_ext.to_str() {
+                if filetype_filter_function(ext) {
+                    file_list.push(path);
+                }
+            }
+        }
+    }
+    file_list
+}
+
+const KEYWORDS_PER_DOCUMENT: usize = 20;
+
+type CompanyResult = (Company, Vec<Download>, Vec<DownloadFailure>);
+
+type CompanyReports = (CompanyMetadata, Vec<Report>);
+
+fn read_reports(
+    file: &File,
+    metadata_directory: &Path,
+    filter: &ReportFilter,
+) -> Result<Option<CompanyReports>, Box<dyn Error>> {
+    let mut rdr = csv::ReaderBuilder::new().delimiter(b';').from_reader(file);
+    let mut selected_reports = Vec::new();
+    for result in rdr.deserialize() {
+        // A single broken row should not make us lose the whole company
+        let report: Report = match result {
+            Ok(report) => report,
+            Err(e) => {
+                error!("Skipping invalid row: {}", e);
+                continue;
+            }
+        };
+        if filter.matches_report(&report) {
+            selected_reports.push(report);
+        }
+    }
+    let metadata = match selected_reports.first() {
+        Some(report) => CompanyMetadata::from_company_name(metadata_directory, &report.company)?,
+        None => return Ok(None),
+    };
+    if !filter.matches_company(&metadata) {
+        return Ok(None);
+    }
+    Ok(Some((metadata, selected_reports)))
+}
+
+async fn iterate_files(
+    root_path: PathBuf,
+    file: &File,
+    metadata_directory: &Path,
+    downloader: Arc<Downloader>,
+    filter: &ReportFilter,
+) -> Result<Option<CompanyResult>, Box<dyn Error>> {
+    let (metadata, selected_reports) = match read_reports(file, metadata_directory, filter)? {
+        Some(company_reports) => company_reports,
+        None => return Ok(None),
+    };
+
+    let mut join_handles = Vec::new();
+    let mut reports = Vec::new();
+    let mut downloads = Vec::new();
+    let mut failures = Vec::new();
+    for report in selected_reports {
+        let downloader = downloader.clone();
+        let root_path = root_path.clone();
+        let download_report = report.clone();
+        let join_handle =
+            tokio::spawn(async move { downloader.download(&root_path, download_report).await });
+        join_handles.push((report, join_handle));
+    }
+    for (report, join_handle) in join_handles {
+        let result = join_handle.await?;
+        match result {
+            Ok(download) => {
+                reports.push(report);
+                downloads.push(download);
+            }
+            Err(e) => {
+                error!(
+                    "Error occurred downloading file {:?}: {}",
+                    report.get_file_path(&root_path),
+                    e
+                );
+                let status = e.downcast_ref::<HttpStatusError>().map(|s| s.0.as_u16());
+                failures.push(DownloadFailure {
+                    report,
+                    error: e.to_string(),
+                    status,
+                });
+            }
+        }
+    }
+    let company = Company::new(metadata, reports);
+    Ok(Some((company, downloads, failures)))
+}
+
+#[derive(StructOpt, Debug)]
+enum Check {
+    /// Check whether the website of each company supports TLS 1.3
+    Tls,
+    /// Check whether the domain of each company has an IPv6 address
+    Ipv6,
+    /// Check whether the domain of each company has a valid SPF record
+    Spf,
+    /// Grade the SPF, DMARC, MX, MTA-STS, TLS-RPT and DKIM records of the domain of each company
+    Email,
+}
+
+#[derive(StructOpt, Debug)]
+enum Command {
+    /// Download all selected documents into a new run and create the website
+    Download,
+    /// Create the website from a download run, by default the most recent one
+    Site { run: Option<PathBuf> },
+    /// Check the source files and the company metadata for errors
+    Validate,
+    /// Check the websites and domains of the companies with metadata
+    Check {
+        /// DNS servers to use: system, google or cloudflare
+        #[structopt(long, default_value = "system")]
+        nameserver: Nameserver,
+
+        #[structopt(subcommand)]
+        check: Check,
+    },
+    /// Compare two download runs, by default the two most recent ones
+    Diff {
+        old_run: Option<PathBuf>,
+        new_run: Option<PathBuf>,
+    },
+    /// Check which links still resolve without downloading the documents
+    CheckLinks {
+        #[structopt(short, long, default_value = "links.json")]
+        output: PathBuf,
+    },
+    /// Extract the text of the documents of a download run, by default the most recent one
+    Extract {
+        run: Option<PathBuf>,
+
+        /// Number of documents extracted at the same time
+        #[structopt(long, default_value = "4")]
+        workers: usize,
+
+        /// Give up on a document after this many seconds, OCR gets this much time per page
+        #[structopt(long, default_value = "300")]
+        timeout_secs: u64,
+    },
+    /// Extract key figures like revenue and net profit from the extracted text of a download run
+    KeyFigures { run: Option<PathBuf> },
+    /// Detect the tables of the documents of a download run and write them as CSV files
+    Tables { run: Option<PathBuf> },
+    /// Build the full-text search index from the extracted text of a download run
+    Index { run: Option<PathBuf> },
+    /// Create a metadata file for every company in the sources that has none yet, the guessed
+    /// fields are marked as unverified
+    InitMetadata,
+    /// Search the extracted text, the filters of the downloader restrict the results
+    Search {
+        query: String,
+
+        /// Maximum number of pages returned
+        #[structopt(short, long, default_value = "20")]
+        limit: usize,
+    },
+}
+
+#[derive(StructOpt, Debug)]
+#[structopt(author, about)]
+struct Options {
+    #[structopt(flatten)]
+    configuration: Configuration,
+
+    #[structopt(subcommand)]
+    command: Command,
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    let options = Options::from_args();
+    let c = options.configuration;
+    match options.command {
+        Command::Download => run_download(c).await,
+        Command::Site { run } => run_site(&c, run),
+        Command::Validate => run_validate(&c),
+        Command::Check { nameserver, check } => run_check(&c, &check, nameserver).await,
+        Command::Diff { old_run, new_run } => run_diff(&c, old_run, new_run),
+        Command::CheckLinks { output } => run_check_links(c, &output).await,
+        Command::Extract {
+            run,
+            workers,
+            timeout_secs,
+        } => run_extract(&c, run, workers, timeout_secs),
+        Command::KeyFigures { run } => run_key_figures(&c, run),
+        Command::Tables { run } => run_tables(&c, run),
+        Command::Index { run } => run_index(&c, run),
+        Command::Search { query, limit } => run_search(&c, &query, limit),
+        Command::InitMetadata => run_init_metadata(&c),
+    }
+}
+
+fn get_runs(download_directory: &Path) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut runs = Vec::new();
+    for entry in fs::read_dir(download_directory)? {
+        let path = entry?.path();
+        let is_run = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .map(|name| NaiveDate::parse_from_str(name, "%Y-%m-%d").is_ok())
+            .unwrap_or(false);
+        if is_run && RunManifest::get_path(&path).exists() {
+            runs.push(path);
+        }
+    }
+    runs.sort();
+    Ok(runs)
+}
+
+fn run_diff(
+    c: &Configuration,
+    old_run: Option<PathBuf>,
+    new_run: Option<PathBuf>,
+) -> Result<(), Box<dyn Error>> {
+    let (old_run, new_run) = match (old_run, new_run) {
+        (Some(old_run), Some(new_run)) => (old_run, new_run),
+        (old_run, None) => {
+            let mut runs = get_runs(Path::new(&c.download_directory))?;
+            let new_run = runs.pop().ok_or("No download runs found")?;
+            let old_run = match old_run {
+                Some(old_run) => old_run,
+                None => runs.pop().ok_or("At least two download runs are needed")?,
+            };
+            (old_run, new_run)
+        }
+        (None, Some(_)) => {
+            return Err("The new run can only be given together with the old run".into())
+        }
+    };
+    println!("Comparing {:?} to {:?}", old_run, new_run);
+    let old_manifest = RunManifest::load(&old_run)?;
+    let new_manifest = RunManifest::load(&new_run)?;
+    let changes = compare_runs(&old_manifest, &new_manifest);
+
+    write_changes_json(&new_run.join("changes.json"), &changes)?;
+    write_changes_csv(&new_run.join("changes.csv"), &changes)?;
+    reporting::create_changes(
+        "html/changes.html",
+        &old_manifest.date,
+        &new_manifest.date,
+        &changes,
+    )?;
+    for change in changes.iter().filter(|c| c.kind != ChangeKind::Unchanged) {
+        println!(
+            "{}, {}, {}-{}: {} {}",
+            change.company,
+            change.year,
+            change.report_type,
+            change.language,
+            change.kind,
+            change.details
+        );
+    }
+    Ok(())
+}
+
+fn get_run_or_latest(c: &Configuration, run: Option<PathBuf>) -> Result<PathBuf, Box<dyn Error>> {
+    match run {
+        Some(run) => Ok(run),
+        None => Ok(get_runs(Path::new(&c.download_directory))?
+            .pop()
+            .ok_or("No download runs found")?),
+    }
+}
+
+// Loads the metadata of each company only once, the filter needs it for the name and the tags
+fn matches_company(
+    c: &Configuration,
+    filter: &ReportFilter,
+    matches: &mut HashMap<String, bool>,
+    company: &str,
+) -> Result<bool, Box<dyn Error>> {
+    if let Some(is_match) = matches.get(company) {
+        return Ok(*is_match);
+    }
+    let metadata = CompanyMetadata::from_company_name(c.get_metadata_directory(), company)?;
+    let is_match = filter.matches_company(&metadata);
+    matches.insert(company.to_owned(), is_match);
+    Ok(is_match)
+}
+
+fn run_key_figures(c: &Configuration, run: Option<PathBuf>) -> Result<(), Box<dyn Error>> {
+    let run = get_run_or_latest(c, run)?;
+    println!("Extracting key figures of {:?}", run);
+    let filter = c.get_filter()?;
+    let manifest = RunManifest::load(&run)?;
+    let extractor = KeyFigureExtractor::new();
+    let mut companies = HashMap::new();
+    let mut candidates = Vec::new();
+    for download in manifest
+        .downloads
+        .iter()
+        .filter(|d| d.validation.is_valid())
+    {
+        let report = &download.report;
+        if !filter.matches_report(report)
+            || !matches_company(c, &filter, &mut companies, &report.company)?
+        {
+            continue;
+        }
+        let text_path = extraction::get_text_path(&report.get_file_path(&run));
+        match extraction::read_pages(&text_path) {
+            Ok(pages) => candidates.extend(extractor.extract(report, &pages)),
+            Err(e) => eprintln!("Skipping {:?}: {}", text_path, e),
+        }
+    }
+    let figures = key_figures::select_best(candidates);
+    key_figures::write_key_figures_csv(&run.join("key_figures.csv"), &figures)?;
+    key_figures::write_key_figures_json(&run.join("key_figures.json"), &figures)?;
+    for figure in &figures {
+        println!(
+            "{}, {}, {}: {} {} ({:.2}, page {} of {}-{})",
+            figure.company,
+            figure.year,
+            figure.figure,
+            figure.value,
+            figure.unit,
+            figure.confidence,
+            figure.page,
+            figure.report_type,
+            figure.language
+        );
+    }
+    Ok(())
+}
+
+fn run_tables(c: &Configuration, run: Option<PathBuf>) -> Result<(), Box<dyn Error>> {
+    let run = get_run_or_latest(c, run)?;
+    println!("Extracting tables of {:?}", run);
+    let filter = c.get_filter()?;
+    let manifest = RunManifest::load(&run)?;
+    let mut companies = HashMap::new();
+    for download in manifest
+        .downloads
+        .iter()
+        .filter(|d| d.validation.is_valid())
+    {
+        let report = &download.report;
+        if !filter.matches_report(report)
+            || !matches_company(c, &filter, &mut companies, &report.company)?
+        {
+            continue;
+        }
+        let file_path = report.get_file_path(&run);
+        let directory = tables::get_tables_directory(&file_path);
+        match tables::extract_tables(&file_path)
+            .and_then(|t| tables::write_tables(&directory, &t).map(|_| t.len()))
+        {
+            Ok(count) => println!("{:?}: {} tables", file_path, count),
+            Err(e) => eprintln!("Extracting tables from {:?} failed: {}", file_path, e),
+        }
+    }
+    Ok(())
+}
+
+fn run_index(c: &Configuration, run: Option<PathBuf>) -> Result<(), Box<dyn Error>> {
+    let run = get_run_or_latest(c, run)?;
+    let index_path = search::get_index_path(Path::new(&c.download_directory));
+    println!("Indexing {:?} into {:?}", run, index_path);
+    let manifest = RunManifest::load(&run)?;
+    let mut tags: HashMap<String, Vec<String>> = HashMap::new();
+    let mut builder = IndexBuilder::create(&index_path)?;
+    let mut indexed = 0;
+    for download in manifest
+        .downloads
+        .iter()
+        .filter(|d| d.validation.is_valid())
+    {
+        let report = &download.report;
+        let text_path = extraction::get_text_path(&report.get_file_path(&run));
+        let pages = match extraction::read_pages(&text_path) {
+            Ok(pages) => pages,
+            Err(e) => {
+                eprintln!("Skipping {:?}: {}", text_path, e);
+                continue;
+            }
+        };
+        if !tags.contains_key(&report.company) {
+            let metadata =
+                CompanyMetadata::from_company_name(c.get_metadata_directory(), &report.company)?;
+            tags.insert(report.company.clone(), metadata.tags);
+        }
+        builder.add_report(report, &tags[&report.company], &pages)?;
+        indexed += 1;
+    }
+    builder.commit()?;
+    println!("Indexed {} documents", indexed);
+    Ok(())
+}
+
+fn run_init_metadata(c: &Configuration) -> Result<(), Box<dyn Error>> {
+    let filter = c.get_filter()?;
+    let metadata_directory = c.get_metadata_directory();
+    fs::create_dir_all(metadata_directory)?;
+    let mut created = 0;
+    for source_file in fs::read_dir(&c.source_directory)? {
+        let file = File::open(source_file?.path())?;
+        let name = match read_reports(&file, metadata_directory, &filter)? {
+            Some((metadata, _)) => metadata.name,
+            None => continue,
+        };
+        let path = CompanyMetadata::get_path(metadata_directory, &name);
+        if path.exists() {
+            continue;
+        }
+        CompanyMetadata::stub(&name).write(metadata_directory)?;
+        println!("Created {:?}", path);
+        created += 1;
+    }
+    println!("Created {} metadata files", created);
+    Ok(())
+}
+
+fn run_search(c: &Configuration, query: &str, limit: usize) -> Result<(), Box<dyn Error>> {
+    let filter = c.get_filter()?;
+    let index = SearchIndex::open(&search::get_index_path(Path::new(&c.download_directory)))?;
+    let hits = index.search(query, &filter, limit)?;
+    if hits.is_empty() && c.format == OutputFormat::Text {
+        println!("No matches");
+    }
+    output::print_records(c.format, &hits)?;
+    Ok(())
+}
+
+fn run_extract(
+    c: &Configuration,
+    run: Option<PathBuf>,
+    workers: usize,
+    timeout_secs: u64,
+) -> Result<(), Box<dyn Error>> {
+    TermLogger::init(
+        LevelFilter::Info,
+        Config::default(),
+        TerminalMode::Mixed,
+        ColorChoice::Auto,
+    )?;
+    let run = get_run_or_latest(c, run)?;
+    println!("Extracting text of {:?}", run);
+    let filter = c.get_filter()?;
+    let manifest = RunManifest::load(&run)?;
+    let mut companies = HashMap::new();
+    let mut jobs = Vec::new();
+    for download in manifest
+        .downloads
+        .iter()
+        .filter(|d| d.validation.is_valid())
+    {
+        let report = &download.report;
+        if filter.matches_report(report)
+            && matches_company(c, &filter, &mut companies, &report.company)?
+        {
+            jobs.push(ExtractionJob::from_download(&run, download));
+        }
+    }
+
+    let extractor = Extractor::new(c, Duration::from_secs(timeout_secs));
+    let mut extraction_manifest = extractor.extract_all(jobs, workers);
+    let extracted_count = extraction_manifest.documents.len();
+    // Keep the records of an earlier extraction that were left out by the filter
+    if let Ok(previous) = ExtractionManifest::load(&run) {
+        for record in previous.documents {
+            let report = &record.report;
+            if !filter.matches_report(report)
+                || !matches_company(c, &filter, &mut companies, &report.company)?
+            {
+                extraction_manifest.documents.push(record);
+            }
+        }
+    }
+    extraction_manifest.write(&run)?;
+
+    let documents = &extraction_manifest.documents[..extracted_count];
+    let successful = documents.iter().filter(|d| d.error.is_none());
+    let cached = successful.clone().filter(|d| d.cached).count();
+    let extracted = successful.filter(|d| !d.cached).count();
+    let failures: Vec<_> = documents.iter().filter(|d| d.error.is_some()).collect();
+    let failed = failures.len();
+    println!(
+        "{} documents, {} extracted, {} from cache, {} failed",
+        documents.len(),
+        extracted,
+        cached,
+        failed
+    );
+    for failure in failures {
+        println!(
+            "{}, {}, {}-{}: {}",
+            failure.report.company,
+            failure.report.year,
+            failure.report.report_type,
+            failure.report.language,
+            failure.error.as_deref().unwrap_or_default()
+        );
+    }
+    Ok(())
+}
+
+async fn run_check_links(c: Configuration, output: &Path) -> Result<(), Box<dyn Error>> {
+    let filter = c.get_filter()?;
+    let downloader = Arc::new(Downloader::new(&c)?);
+
+    let mut join_handles = Vec::new();
+    for source_file in fs::read_dir(&c.source_directory)? {
+        let source_path = source_file?.path();
+        let file = File::open(&source_path)?;
+        let reports = match read_reports(&file, c.get_metadata_directory(), &filter) {
+            Ok(Some((_, reports))) => reports,
+            Ok(None) => continue,
+            Err(e) => {
+                eprintln!("Error reading {:?}: {}", source_path, e);
+                continue;
+            }
+        };
+        for report in reports {
+            let downloader = downloader.clone();
+            join_handles.push(tokio::spawn(
+                async move { downloader.check_link(report).await },
+            ));
+        }
+    }
+    let mut statuses = Vec::new();
+    for join_handle in join_handles {
+        match join_handle.await? {
+            Ok(status) => statuses.push(status),
+            Err(e) => eprintln!("Error checking link: {}", e),
+        }
+    }
+    statuses.sort_by_key(|s| {
+        (
+            s.report.company.clone(),
+            std::cmp::Reverse(s.report.year),
+            s.report.report_type.clone(),
+            s.report.language.clone(),
+        )
+    });
+
+    link_check::write_link_statuses(output, &statuses)?;
+    reporting::create_link_report("html/links.html", &statuses)?;
+    for (company, company_statuses) in link_check::group_problems(&statuses) {
+        println!("{}", company);
+        for status in company_statuses {
+            println!(
+                "  {} {}-{}: {} ({})",
+                status.report.year,
+                status.report.report_type,
+                status.report.language,
+                status.get_problem().unwrap_or_default(),
+                status.report.link
+            );
+        }
+    }
+    Ok(())
+}
+
+async fn run_download(c: Configuration) -> Result<(), Box<dyn Error>> {
+    let filter = Arc::new(c.get_filter()?);
+    let now = Utc::now();
+    let date = format!("{}-{:02}-{:02}", now.year(), now.month(), now.day());
+
+    let download_directory = format!("{}/{}", c.download_directory, date);
+    let log_file = format!("{}/output.txt", download_directory);
+    let root_path = PathBuf::from(&download_directory);
+    fs::create_dir_all(&root_path).unwrap();
+    let mut downloader = Downloader::new(&c)?;
+    if RunManifest::get_path(&root_path).exists() {
+        downloader.set_previous_run(&root_path, &RunManifest::load(&root_path)?);
+    }
+    let downloader = Arc::new(downloader);
+    let source_path = Path::new(&c.source_directory);
+
+    CombinedLogger::init(vec![
+        TermLogger::new(
+            LevelFilter::Info,
+            Config::default(),
+            TerminalMode::Mixed,
+            ColorChoice::Auto,
+        ),
+        WriteLogger::new(
+            LevelFilter::Error,
+            Config::default(),
+            File::create(log_file).unwrap(),
+        ),
+    ])
+    .unwrap();
+
+    println!(
+        "Downloading into {:?} from source directory {:?}",
+        root_path, source_path
+    );
+    let paths = fs::read_dir(source_path).unwrap();
+
+    let mut join_handles = Vec::new();
+    for source_file in paths {
+        let my_root_path = root_path.clone();
+        let filter = filter.clone();
+        let downloader = downloader.clone();
+        let metadata_directory = PathBuf::from(&c.metadata_directory);
+        let join_handle = tokio::spawn(async move {
+            let source_file = source_file.unwrap();
+            println!("Processing: {}", source_file.path().display());
+            let file = File::open(source_file.path())
+                .unwrap_or_else(|_| panic!("Error opening file {:?}", &source_file.path()));
+            let path = PathBuf::from(&my_root_path);
+            let result = iterate_files(path, &file, &metadata_directory, downloader, &filter).await;
+            match result {
+                Ok(reports) => reports,
+                Err(e) => {
+                    error!("Error reading {:?}: {}", source_file.path(), e);
+                    None
+                }
+            }
+        });
+        join_handles.push(join_handle);
+    }
+    let mut companies = Vec::new();
+    let mut manifest = RunManifest {
+        date: date.clone(),
+        ..Default::default()
+    };
+    for join_handle in join_handles {
+        let result = join_handle.await?;
+        match result {
+            Some((company, mut downloads, failures)) => {
+                manifest.downloads.extend(downloads.iter().cloned());
+                manifest.failures.extend(failures);
+                downloads.sort_by_key(|d| std::cmp::Reverse(d.report.year));
+                let company_download = CompanyDownloads { company, downloads };
+                companies.push(company_download);
+            }
+            None => debug!("Skipping source file"),
+        }
+    }
+
+    manifest.write(&root_path)?;
+    create_site(&c, &manifest, companies)
+}
+
+fn create_site(
+    c: &Configuration,
+    manifest: &RunManifest,
+    mut companies: Vec<CompanyDownloads>,
+) -> Result<(), Box<dyn Error>> {
+    companies.sort_by(|a, b| a.company.metadata.name.cmp(&b.company.metadata.name));
+    let tags = ["SMI", "SMIM", "Bank", "Kantonalbank", "Insurance"];
+    let empty_tags = Vec::<&str>::new();
+    for t in &tags {
+        let smi_list = filter_companies(t, &companies);
+        let path = format!("html/{}.html", &t);
+        reporting::create_index(&path, &smi_list, &empty_tags)?;
+    }
+    let keywords = extraction::load_keywords(c, &manifest.downloads, KEYWORDS_PER_DOCUMENT);
+    reporting::create_reports(&companies, &tags, &keywords)?;
+    Ok(())
+}
+
+fn run_site(c: &Configuration, run: Option<PathBuf>) -> Result<(), Box<dyn Error>> {
+    let run = get_run_or_latest(c, run)?;
+    println!("Creating the website from {:?}", run);
+    let filter = c.get_filter()?;
+    let manifest = RunManifest::load(&run)?;
+    let mut downloads: BTreeMap<&str, Vec<Download>> = BTreeMap::new();
+    for download in &manifest.downloads {
+        if filter.matches_report(&download.report) {
+            downloads
+                .entry(&download.report.company)
+                .or_default()
+                .push(download.clone());
+        }
+    }
+    let mut companies = Vec::new();
+    for (name, mut downloads) in downloads {
+        let metadata = CompanyMetadata::from_company_name(c.get_metadata_directory(), name)?;
+        if !filter.matches_company(&metadata) {
+            continue;
+        }
+        downloads.sort_by_key(|d| std::cmp::Reverse(d.report.year));
+        let reports = downloads.iter().map(|d| d.report.clone()).collect();
+        let company = Company::new(metadata, reports);
+        companies.push(CompanyDownloads { company, downloads });
+    }
+    create_site(c, &manifest, companies)
+}
+
+fn run_validate(c: &Configuration) -> Result<(), Box<dyn Error>> {
+    let problems = schema::check_all(Path::new(&c.source_directory), c.get_metadata_directory());
+    output::print_records(c.format, &problems)?;
+    if !problems.is_empty() {
+        eprintln!("{} problems found", problems.len());
+        process::exit(1);
+    }
+    Ok(())
+}
+
+async fn run_check(
+    c: &Configuration,
+    check: &Check,
+    nameserver: Nameserver,
+) -> Result<(), Box<dyn Error>> {
+    let filter = c.get_filter()?;
+    let (metas, errors) = get_metadata(c.get_metadata_directory(), |meta| {
+        filter.matches_company(meta)
+    })?;
+    for error in &errors {
+        eprintln!("Skipping {}", error);
+    }
+    let mut records = Vec::new();
+    match check {
+        Check::Tls => {
+            let client = checks::create_tls13_client(Duration::from_secs(c.connect_timeout_secs))?;
+            for meta in metas.iter().filter(|meta| !meta.url.is_empty()) {
+                records.push(checks::check_tls(&client, meta).await);
+            }
+        }
+        Check::Ipv6 => {
+            let resolver = nameserver.create_resolver()?;
+            for meta in metas.iter().filter(|meta| !meta.url.is_empty()) {
+                records.push(checks::check_ipv6(&resolver, meta).await);
+            }
+        }
+        Check::Spf => {
+            let resolver = nameserver.create_resolver()?;
+            for meta in metas.iter().filter(|meta| !meta.url.is_empty()) {
+                records.push(checks::check_spf(&resolver, meta).await);
+            }
+        }
+        Check::Email => {
+            let resolver = nameserver.create_resolver()?;
+            let mut audits = Vec::new();
+            for meta in metas.iter().filter(|meta| !meta.url.is_empty()) {
+                audits.push(email_security::audit_email(&resolver, meta).await);
+            }
+            match c.format {
+                OutputFormat::Text => {
+                    email_security::write_audit_table(io::stdout().lock(), &audits)?
+                }
+                format => output::print_records(format, &audits)?,
+            }
+            return Ok(());
+        }
+    }
+    output::print_records(c.format, &records)?;
+    Ok(())
+}
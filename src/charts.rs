@@ -0,0 +1,155 @@
+use std::fmt::Write;
+
+const WIDTH: f64 = 640.0;
+const HEIGHT: f64 = 260.0;
+const LEFT: f64 = 50.0;
+const RIGHT: f64 = 130.0;
+const TOP: f64 = 30.0;
+const BOTTOM: f64 = 30.0;
+const TICKS: u32 = 4;
+const COLORS: [&str; 8] = [
+    "#1f77b4", "#ff7f0e", "#2ca02c", "#d62728", "#9467bd", "#8c564b", "#e377c2", "#7f7f7f",
+];
+
+pub struct Series<'a> {
+    pub label: &'a str,
+    pub points: Vec<(u16, f64)>,
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// Rounds the maximum up so that the axis has ticks at 1, 2 or 5 times a power of ten
+fn get_tick_step(max: f64) -> f64 {
+    if max <= 0.0 {
+        return 1.0;
+    }
+    let rough = max / f64::from(TICKS);
+    let magnitude = 10f64.powf(rough.log10().floor());
+    [1.0, 2.0, 5.0, 10.0]
+        .iter()
+        .map(|f| f * magnitude)
+        .find(|step| *step >= rough)
+        .unwrap_or(10.0 * magnitude)
+}
+
+fn format_value(value: f64) -> String {
+    if value.fract() == 0.0 || value >= 100.0 {
+        format!("{:.0}", value)
+    } else {
+        format!("{:.1}", value)
+    }
+}
+
+/// Renders the series as an inline SVG line chart with the years on the x axis
+pub fn line_chart(title: &str, series: &[Series]) -> String {
+    let years: Vec<u16> = series
+        .iter()
+        .flat_map(|s| s.points.iter().map(|p| p.0))
+        .collect();
+    let (first_year, last_year) = match (years.iter().min(), years.iter().max()) {
+        (Some(first), Some(last)) => (*first, *last),
+        _ => return String::new(),
+    };
+    let max = series
+        .iter()
+        .flat_map(|s| s.points.iter().map(|p| p.1))
+        .fold(0.0, f64::max);
+    let step = get_tick_step(max);
+    let y_max = step * f64::from(TICKS);
+    let plot_width = WIDTH - LEFT - RIGHT;
+    let plot_height = HEIGHT - TOP - BOTTOM;
+    let x = |year: u16| match last_year - first_year {
+        0 => LEFT + plot_width / 2.0,
+        span => LEFT + plot_width * f64::from(year - first_year) / f64::from(span),
+    };
+    let y = |value: f64| TOP + plot_height * (1.0 - value / y_max);
+
+    let mut svg = String::new();
+    write!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}" font-family="Consolas" font-size="11">"#,
+        w = WIDTH,
+        h = HEIGHT
+    )
+    .unwrap();
+    write!(
+        svg,
+        r#"<text x="{}" y="16" font-size="13">{}</text>"#,
+        LEFT,
+        escape(title)
+    )
+    .unwrap();
+    for tick in 0..=TICKS {
+        let value = step * f64::from(tick);
+        write!(
+            svg,
+            r##"<line x1="{x1}" y1="{y:.1}" x2="{x2}" y2="{y:.1}" stroke="#ddd"/><text x="{tx}" y="{ty:.1}" text-anchor="end">{v}</text>"##,
+            x1 = LEFT,
+            x2 = WIDTH - RIGHT,
+            y = y(value),
+            tx = LEFT - 5.0,
+            ty = y(value) + 4.0,
+            v = format_value(value)
+        )
+        .unwrap();
+    }
+    // Label at most about ten years so that they don't overlap
+    let year_step = usize::from((last_year - first_year) / 10 + 1);
+    for year in (first_year..=last_year).step_by(year_step) {
+        write!(
+            svg,
+            r#"<text x="{:.1}" y="{}" text-anchor="middle">{}</text>"#,
+            x(year),
+            HEIGHT - BOTTOM + 16.0,
+            year
+        )
+        .unwrap();
+    }
+    for (i, s) in series.iter().enumerate() {
+        let color = COLORS[i % COLORS.len()];
+        let points: Vec<String> = s
+            .points
+            .iter()
+            .map(|(year, value)| format!("{:.1},{:.1}", x(*year), y(*value)))
+            .collect();
+        write!(
+            svg,
+            r#"<polyline fill="none" stroke="{}" stroke-width="2" points="{}"/>"#,
+            color,
+            points.join(" ")
+        )
+        .unwrap();
+        for (year, value) in &s.points {
+            write!(
+                svg,
+                r#"<circle cx="{:.1}" cy="{:.1}" r="3" fill="{}"><title>{} {}: {}</title></circle>"#,
+                x(*year),
+                y(*value),
+                color,
+                escape(s.label),
+                year,
+                format_value(*value)
+            )
+            .unwrap();
+        }
+        let legend_y = TOP + 16.0 * i as f64;
+        write!(
+            svg,
+            r#"<rect x="{}" y="{}" width="10" height="10" fill="{}"/><text x="{}" y="{}">{}</text>"#,
+            WIDTH - RIGHT + 10.0,
+            legend_y,
+            color,
+            WIDTH - RIGHT + 25.0,
+            legend_y + 9.0,
+            escape(s.label)
+        )
+        .unwrap();
+    }
+    svg.push_str("</svg>");
+    svg
+}
@@ -0,0 +1,249 @@
+use chrono::{SecondsFormat, Utc};
+use reqwest::tls::Version;
+use serde_derive::Serialize;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+use trust_dns_resolver::config::{ResolverConfig, ResolverOpts};
+use trust_dns_resolver::error::ResolveError;
+use trust_dns_resolver::TokioAsyncResolver;
+use viaspf::lookup::{Lookup, LookupError, LookupResult, Name};
+use viaspf_record::Record;
+
+use crate::data_structures::CompanyMetadata;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Nameserver {
+    /// The resolvers configured on this system
+    System,
+    Google,
+    Cloudflare,
+}
+
+impl Nameserver {
+    pub fn create_resolver(&self) -> Result<TokioAsyncResolver, ResolveError> {
+        match self {
+            Nameserver::System => TokioAsyncResolver::tokio_from_system_conf(),
+            Nameserver::Google => {
+                TokioAsyncResolver::tokio(ResolverConfig::google(), ResolverOpts::default())
+            }
+            Nameserver::Cloudflare => {
+                TokioAsyncResolver::tokio(ResolverConfig::cloudflare(), ResolverOpts::default())
+            }
+        }
+    }
+}
+
+impl fmt::Display for Nameserver {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Nameserver::System => "system",
+            Nameserver::Google => "google",
+            Nameserver::Cloudflare => "cloudflare",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Nameserver {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "system" => Ok(Nameserver::System),
+            "google" => Ok(Nameserver::Google),
+            "cloudflare" => Ok(Nameserver::Cloudflare),
+            _ => Err(format!(
+                "Unknown nameserver '{}', expected system, google or cloudflare",
+                name
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckResult {
+    Pass,
+    Fail,
+    /// The check could not be done, e.g. because the DNS server did not answer
+    Error,
+}
+
+impl fmt::Display for CheckResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            CheckResult::Pass => "pass",
+            CheckResult::Fail => "fail",
+            CheckResult::Error => "error",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Result of one check of one company, the same for all checks so that the results can be
+/// collected over time and joined with the metadata by the company name
+#[derive(Debug, Serialize, Clone)]
+pub struct CheckRecord {
+    pub company: String,
+    pub domain: String,
+    pub check: &'static str,
+    pub result: CheckResult,
+    pub details: String,
+    pub timestamp: String,
+}
+
+impl CheckRecord {
+    pub fn new(
+        metadata: &CompanyMetadata,
+        domain: &str,
+        check: &'static str,
+        result: CheckResult,
+        details: String,
+    ) -> CheckRecord {
+        CheckRecord {
+            company: metadata.name.clone(),
+            domain: domain.to_owned(),
+            check,
+            result,
+            details,
+            timestamp: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+        }
+    }
+}
+
+impl fmt::Display for CheckRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}, {}, {}: {}",
+            self.company, self.domain, self.check, self.result
+        )?;
+        if !self.details.is_empty() {
+            write!(f, " ({})", self.details)?;
+        }
+        Ok(())
+    }
+}
+
+pub async fn check_ipv6<L>(lookup: &L, metadata: &CompanyMetadata) -> CheckRecord
+where
+    L: Lookup + ?Sized,
+{
+    let domain = metadata.get_domainname(false);
+    let addresses = match Name::new(&domain) {
+        Ok(name) => lookup.lookup_aaaa(&name).await,
+        Err(e) => Err(LookupError::Dns(Some(Box::new(e)))),
+    };
+    let (result, details) = match addresses {
+        Ok(addresses) => {
+            let addresses: Vec<String> = addresses.iter().map(|a| a.to_string()).collect();
+            (CheckResult::Pass, addresses.join(" "))
+        }
+        Err(LookupError::NoRecords) => (CheckResult::Fail, "no AAAA record".to_owned()),
+        Err(e) => (CheckResult::Error, e.to_string()),
+    };
+    CheckRecord::new(metadata, &domain, "ipv6", result, details)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SpfStatus {
+    Valid(String),
+    Invalid(String),
+    Multiple(Vec<String>),
+    Missing,
+    LookupFailed(String),
+}
+
+/// Looks up the TXT records of a name given as string
+pub async fn lookup_txt<L>(lookup: &L, name: &str) -> LookupResult<Vec<String>>
+where
+    L: Lookup + ?Sized,
+{
+    let name = Name::new(name).map_err(|e| LookupError::Dns(Some(Box::new(e))))?;
+    lookup.lookup_txt(&name).await
+}
+
+/// Looks up the SPF record of the domain and checks its syntax
+pub async fn get_spf_status<L>(lookup: &L, domain: &str) -> SpfStatus
+where
+    L: Lookup + ?Sized,
+{
+    let mut records = match lookup_txt(lookup, domain).await {
+        Ok(records) => records,
+        Err(LookupError::NoRecords) => return SpfStatus::Missing,
+        Err(e) => return SpfStatus::LookupFailed(e.to_string()),
+    };
+    records.retain(|r| r == "v=spf1" || r.starts_with("v=spf1 "));
+    match records.len() {
+        0 => SpfStatus::Missing,
+        1 => {
+            let record = records.remove(0);
+            if record.parse::<Record>().is_ok() {
+                SpfStatus::Valid(record)
+            } else {
+                SpfStatus::Invalid(record)
+            }
+        }
+        // More than one SPF record is a permanent error for receivers (RFC 7208, 4.5)
+        _ => SpfStatus::Multiple(records),
+    }
+}
+
+pub async fn check_spf<L>(lookup: &L, metadata: &CompanyMetadata) -> CheckRecord
+where
+    L: Lookup + ?Sized,
+{
+    let domain = metadata.get_domainname(true);
+    let (result, details) = match get_spf_status(lookup, &domain).await {
+        SpfStatus::Valid(record) => (CheckResult::Pass, record),
+        SpfStatus::Invalid(record) => (CheckResult::Fail, format!("invalid record: {}", record)),
+        SpfStatus::Multiple(records) => (
+            CheckResult::Fail,
+            format!("multiple records: {}", records.join(" | ")),
+        ),
+        SpfStatus::Missing => (CheckResult::Fail, "no SPF record".to_owned()),
+        SpfStatus::LookupFailed(e) => (CheckResult::Error, e),
+    };
+    CheckRecord::new(metadata, &domain, "spf", result, details)
+}
+
+/// Client that only accepts TLS 1.3 connections and gives up on a website after the timeout
+pub fn create_tls13_client(timeout: Duration) -> reqwest::Result<reqwest::Client> {
+    reqwest::ClientBuilder::new()
+        .use_rustls_tls()
+        .min_tls_version(Version::TLS_1_3)
+        .connect_timeout(timeout)
+        .timeout(timeout)
+        .build()
+}
+
+// The message of a reqwest error often only says that the request failed, the cause (e.g. the
+// TLS handshake) is in its sources. Some errors already include the message of their source.
+fn get_error_chain(e: &dyn std::error::Error) -> String {
+    let mut message = e.to_string();
+    let mut source = e.source();
+    while let Some(cause) = source {
+        let cause_message = cause.to_string();
+        if !message.contains(&cause_message) {
+            message = format!("{}: {}", message, cause_message);
+        }
+        source = cause.source();
+    }
+    message
+}
+
+pub async fn check_tls(client: &reqwest::Client, metadata: &CompanyMetadata) -> CheckRecord {
+    let domain = metadata.get_domainname(false);
+    let (result, details) = match client.get(&metadata.url).send().await {
+        Ok(response) => (
+            CheckResult::Pass,
+            format!("TLS 1.3, HTTP {}", response.status().as_u16()),
+        ),
+        // A website that doesn't answer in time says nothing about its TLS support
+        Err(e) if e.is_timeout() => (CheckResult::Error, get_error_chain(&e)),
+        Err(e) if e.is_connect() => (CheckResult::Fail, get_error_chain(&e)),
+        Err(e) => (CheckResult::Error, get_error_chain(&e)),
+    };
+    CheckRecord::new(metadata, &domain, "tls", result, details)
+}
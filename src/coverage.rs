@@ -0,0 +1,348 @@
+use serde_derive::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::path::Path;
+
+use crate::data_structures::{Company, Language, ReportType};
+use crate::error::{Error, Result};
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum GapKind {
+    /// Years without the document between two years with it
+    Missing,
+    /// The document was published until some year but not for the newest years of the company
+    Stopped,
+    /// Years in which the report type was published in other languages, but not in this one
+    /// although the company used it for other documents of the same year
+    Untranslated,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct Gap {
+    pub company: String,
+    pub report_type: ReportType,
+    pub language: Language,
+    pub kind: GapKind,
+    pub from_year: u16,
+    pub to_year: u16,
+}
+
+impl fmt::Display for Gap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}-{} ", self.report_type, self.language)?;
+        match self.kind {
+            GapKind::Stopped => write!(f, "stopped after {}", self.from_year - 1),
+            GapKind::Missing if self.from_year == self.to_year => {
+                write!(f, "missing for {}", self.from_year)
+            }
+            GapKind::Missing => write!(f, "missing for {}-{}", self.from_year, self.to_year),
+            GapKind::Untranslated if self.from_year == self.to_year => {
+                write!(f, "only in other languages for {}", self.from_year)
+            }
+            GapKind::Untranslated => write!(
+                f,
+                "only in other languages for {}-{}",
+                self.from_year, self.to_year
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoverageStatus {
+    Present,
+    Missing,
+    NotExpected,
+}
+
+/// Which documents a company published per year, compared to what is expected from its other years
+pub struct Coverage {
+    pub company: String,
+    pub oldest_year: u16,
+    pub newest_year: u16,
+    /// Years with each combination of report type and language listed in the sources, also
+    /// combinations that are only expected from the languages of the company
+    pub documents: BTreeMap<(ReportType, Language), BTreeSet<u16>>,
+    /// Years in which a combination is expected because the report type was published in
+    /// another language and the company used this language for other documents
+    pub untranslated: BTreeMap<(ReportType, Language), BTreeSet<u16>>,
+    pub gaps: Vec<Gap>,
+}
+
+impl Coverage {
+    pub fn from_company(company: &Company) -> Coverage {
+        let mut documents: BTreeMap<(ReportType, Language), BTreeSet<u16>> = BTreeMap::new();
+        for report in &company.reports {
+            documents
+                .entry((report.report_type.clone(), report.language.clone()))
+                .or_default()
+                .insert(report.year);
+        }
+        let untranslated = find_untranslated(&documents);
+        for key in untranslated.keys() {
+            documents.entry(key.clone()).or_default();
+        }
+        let mut coverage = Coverage {
+            company: company.metadata.name.clone(),
+            oldest_year: company.oldest_year,
+            newest_year: company.newest_year,
+            documents,
+            untranslated,
+            gaps: Vec::new(),
+        };
+        coverage.gaps = coverage.find_gaps();
+        coverage
+    }
+
+    // A document is expected from the first year it was published until the newest year of
+    // the company
+    fn find_gaps(&self) -> Vec<Gap> {
+        let mut gaps = Vec::new();
+        for ((report_type, language), years) in &self.documents {
+            let (first, last) = match (years.iter().next(), years.iter().next_back()) {
+                (Some(first), Some(last)) => (*first, *last),
+                _ => continue,
+            };
+            let mut gap = |kind, from_year, to_year| {
+                gaps.push(Gap {
+                    company: self.company.clone(),
+                    report_type: report_type.clone(),
+                    language: language.clone(),
+                    kind,
+                    from_year,
+                    to_year,
+                })
+            };
+            let mut previous = first;
+            for year in years.iter().skip(1) {
+                if *year > previous + 1 {
+                    gap(GapKind::Missing, previous + 1, year - 1);
+                }
+                previous = *year;
+            }
+            if last < self.newest_year {
+                gap(GapKind::Stopped, last + 1, self.newest_year);
+            }
+        }
+        for ((report_type, language), years) in &self.untranslated {
+            let mut years = years.iter();
+            let mut from_year = match years.next() {
+                Some(year) => *year,
+                None => continue,
+            };
+            let mut to_year = from_year;
+            let mut gap = |from_year, to_year| {
+                gaps.push(Gap {
+                    company: self.company.clone(),
+                    report_type: report_type.clone(),
+                    language: language.clone(),
+                    kind: GapKind::Untranslated,
+                    from_year,
+                    to_year,
+                })
+            };
+            for year in years {
+                if *year > to_year + 1 {
+                    gap(from_year, to_year);
+                    from_year = *year;
+                }
+                to_year = *year;
+            }
+            gap(from_year, to_year);
+        }
+        gaps
+    }
+
+    pub fn get_status(
+        &self,
+        year: u16,
+        report_type: &ReportType,
+        language: &Language,
+    ) -> CoverageStatus {
+        let years = match self.documents.get(&(report_type.clone(), language.clone())) {
+            Some(years) => years,
+            None => return CoverageStatus::NotExpected,
+        };
+        let is_untranslated = |year| {
+            self.untranslated
+                .get(&(report_type.clone(), language.clone()))
+                .is_some_and(|years| years.contains(&year))
+        };
+        if years.contains(&year) {
+            CoverageStatus::Present
+        } else if years.iter().next().is_some_and(|first| year > *first) || is_untranslated(year) {
+            CoverageStatus::Missing
+        } else {
+            CoverageStatus::NotExpected
+        }
+    }
+}
+
+// A report type is expected in every language the company used in a year, e.g. the
+// sustainability report in English if the annual report of that year is in German and English.
+// Years after the first document of a combination are already expected from its own history.
+fn find_untranslated(
+    documents: &BTreeMap<(ReportType, Language), BTreeSet<u16>>,
+) -> BTreeMap<(ReportType, Language), BTreeSet<u16>> {
+    let mut languages: BTreeMap<u16, BTreeSet<Language>> = BTreeMap::new();
+    let mut report_types: BTreeMap<ReportType, BTreeSet<u16>> = BTreeMap::new();
+    for ((report_type, language), years) in documents {
+        for year in years {
+            languages.entry(*year).or_default().insert(language.clone());
+        }
+        report_types
+            .entry(report_type.clone())
+            .or_default()
+            .extend(years);
+    }
+    let mut untranslated: BTreeMap<(ReportType, Language), BTreeSet<u16>> = BTreeMap::new();
+    for (report_type, years) in &report_types {
+        for year in years {
+            for language in &languages[year] {
+                let key = (report_type.clone(), language.clone());
+                let first = documents.get(&key).and_then(|y| y.iter().next());
+                if first.is_some_and(|first| year >= first) {
+                    continue;
+                }
+                untranslated.entry(key).or_default().insert(*year);
+            }
+        }
+    }
+    untranslated
+}
+
+#[derive(Serialize)]
+struct GapRow<'a> {
+    company: &'a str,
+    report_type: &'a ReportType,
+    language: &'a Language,
+    kind: GapKind,
+    from_year: u16,
+    to_year: u16,
+    description: String,
+    url: &'a str,
+}
+
+/// Writes all gaps as a work list, together with the website of the company to look for them
+pub fn write_gaps_csv(path: &Path, gaps: &[(&Gap, &str)]) -> Result<()> {
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(b';')
+        .from_path(path)
+        .map_err(Error::csv(path))?;
+    for (gap, url) in gaps {
+        writer
+            .serialize(GapRow {
+                company: &gap.company,
+                report_type: &gap.report_type,
+                language: &gap.language,
+                kind: gap.kind,
+                from_year: gap.from_year,
+                to_year: gap.to_year,
+                description: gap.to_string(),
+                url,
+            })
+            .map_err(Error::csv(path))?;
+    }
+    writer.flush().map_err(Error::io(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_structures::{CompanyMetadata, Report};
+
+    fn create_company(documents: &[(ReportType, Language, u16)]) -> Company {
+        let reports = documents
+            .iter()
+            .map(|(report_type, language, year)| Report {
+                company: "Example".to_owned(),
+                language: language.clone(),
+                report_type: report_type.clone(),
+                year: *year,
+                link: format!(
+                    "https://example.ch/{}-{}-{}.pdf",
+                    year, report_type, language
+                ),
+                alternative_links: Vec::new(),
+            })
+            .collect();
+        Company::new(CompanyMetadata::new("Example"), reports)
+    }
+
+    fn get_descriptions(coverage: &Coverage) -> Vec<String> {
+        coverage.gaps.iter().map(|g| g.to_string()).collect()
+    }
+
+    #[test]
+    fn gaps_within_the_history_of_a_document_are_found() {
+        let ar = ReportType::AnnualReport;
+        let company = create_company(&[
+            (ar.clone(), Language::DE, 2019),
+            (ar.clone(), Language::DE, 2022),
+            (ar.clone(), Language::DE, 2023),
+            (ar.clone(), Language::EN, 2019),
+            (ar.clone(), Language::EN, 2020),
+            (ar.clone(), Language::EN, 2021),
+            (ar.clone(), Language::EN, 2022),
+            (ar.clone(), Language::EN, 2023),
+            (ar.clone(), Language::FR, 2020),
+        ]);
+        let coverage = Coverage::from_company(&company);
+        assert_eq!(
+            get_descriptions(&coverage),
+            vec!["AR-DE missing for 2020-2021", "AR-FR stopped after 2020"]
+        );
+        assert_eq!(
+            coverage.get_status(2020, &ar, &Language::DE),
+            CoverageStatus::Missing
+        );
+        // The company did not publish anything in French before 2020
+        assert_eq!(
+            coverage.get_status(2019, &ar, &Language::FR),
+            CoverageStatus::NotExpected
+        );
+        assert_eq!(
+            coverage.get_status(2023, &ar, &Language::IT),
+            CoverageStatus::NotExpected
+        );
+    }
+
+    #[test]
+    fn report_types_are_expected_in_all_languages_of_the_company() {
+        let ar = ReportType::AnnualReport;
+        let sr = ReportType::SustainabilityReport;
+        let company = create_company(&[
+            (ar.clone(), Language::DE, 2020),
+            (ar.clone(), Language::EN, 2020),
+            (ar.clone(), Language::DE, 2021),
+            (ar.clone(), Language::EN, 2021),
+            (ar.clone(), Language::DE, 2023),
+            (ar.clone(), Language::EN, 2023),
+            (sr.clone(), Language::DE, 2020),
+            (sr.clone(), Language::DE, 2021),
+            (sr.clone(), Language::DE, 2023),
+        ]);
+        let coverage = Coverage::from_company(&company);
+        assert_eq!(
+            get_descriptions(&coverage),
+            vec![
+                "AR-EN missing for 2022",
+                "AR-DE missing for 2022",
+                "SR-DE missing for 2022",
+                "SR-EN only in other languages for 2020-2021",
+                "SR-EN only in other languages for 2023",
+            ]
+        );
+        assert!(coverage.documents.contains_key(&(sr.clone(), Language::EN)));
+        assert_eq!(
+            coverage.get_status(2021, &sr, &Language::EN),
+            CoverageStatus::Missing
+        );
+        // The company published nothing in 2022, so no language is known for it
+        assert_eq!(
+            coverage.get_status(2022, &sr, &Language::EN),
+            CoverageStatus::NotExpected
+        );
+    }
+}
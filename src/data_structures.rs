@@ -1,81 +1,241 @@
+use chrono::NaiveDate;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize as _, Deserializer, Serializer};
 use serde_derive::{Deserialize, Serialize};
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use structopt::StructOpt;
 
-use crate::reporting::write_metadata;
+use crate::downloader::RetryPolicy;
+use crate::error::{self, Error};
+use crate::extraction::Backend;
+use crate::output::OutputFormat;
+use crate::pdf_metadata::PdfMetadata;
+pub use crate::types::{Language, ReportType};
+use crate::validation::Validation;
 
 #[derive(StructOpt, Debug)]
 #[structopt(author, about)]
 pub struct Configuration {
-    #[structopt(short, long, default_value = "Sources/")]
+    #[structopt(
+        short,
+        long = "sources-dir",
+        alias = "source-directory",
+        default_value = "Sources/",
+        global = true
+    )]
     pub source_directory: String,
 
-    #[structopt(short, long, default_value = "downloads/")]
+    #[structopt(short, long, default_value = "downloads/", global = true)]
     pub download_directory: String,
+
+    #[structopt(
+        short,
+        long = "metadata-dir",
+        alias = "metadata-directory",
+        default_value = "metadata/",
+        global = true
+    )]
+    pub metadata_directory: String,
+
+    /// Only process companies whose name matches this regular expression (case insensitive)
+    #[structopt(long, global = true)]
+    pub company: Option<String>,
+
+    /// Only process companies with one of these tags
+    #[structopt(long, number_of_values = 1, global = true)]
+    pub tag: Vec<String>,
+
+    /// Only process reports from this year on
+    #[structopt(long, global = true)]
+    pub from_year: Option<u16>,
+
+    /// Only process reports up to this year
+    #[structopt(long, global = true)]
+    pub to_year: Option<u16>,
+
+    /// Only process reports of these types (e.g. AR, SR)
+    #[structopt(long, number_of_values = 1, global = true)]
+    pub report_type: Vec<ReportType>,
+
+    /// Only process reports in these languages (e.g. EN, DE)
+    #[structopt(long, number_of_values = 1, global = true)]
+    pub language: Vec<Language>,
+
+    /// How many times a failed download is retried
+    #[structopt(long, default_value = "3", global = true)]
+    pub max_retries: u32,
+
+    /// Backoff before the first retry in milliseconds, doubled for every further retry
+    #[structopt(long, default_value = "1000", global = true)]
+    pub initial_backoff_ms: u64,
+
+    /// Upper limit for the backoff between retries in milliseconds
+    #[structopt(long, default_value = "30000", global = true)]
+    pub max_backoff_ms: u64,
+
+    /// Seconds to wait for a connection to a server, in the TLS check also for its answer
+    #[structopt(long, default_value = "30", global = true)]
+    pub connect_timeout_secs: u64,
+
+    /// Seconds without receiving any data after which a download attempt is aborted and retried
+    #[structopt(long, default_value = "60", global = true)]
+    pub read_timeout_secs: u64,
+
+    /// Maximum number of downloads running at the same time
+    #[structopt(long, default_value = "8", global = true)]
+    pub max_concurrent_downloads: usize,
+
+    /// Maximum number of downloads running at the same time from the same host
+    #[structopt(long, default_value = "2", global = true)]
+    pub max_downloads_per_host: usize,
+
+    /// Minimum delay between two requests to the same host in milliseconds
+    #[structopt(long, default_value = "1000", global = true)]
+    pub host_delay_ms: u64,
+
+    #[structopt(
+        long,
+        default_value = "annual_report_database (+https://github.com/Niederb/annual_report_database)",
+        global = true
+    )]
+    pub user_agent: String,
+
+    /// Text extractors to try in this order: pdftotext, lopdf, pdf-extract
+    #[structopt(
+        long = "extractor",
+        default_value = "pdftotext,lopdf,pdf-extract",
+        use_delimiter = true,
+        global = true
+    )]
+    pub extractors: Vec<Backend>,
+
+    /// Do not OCR pages without text, even if tesseract is installed
+    #[structopt(long, global = true)]
+    pub no_ocr: bool,
+
+    /// Output format of commands that list results: text, json (one object per line) or csv
+    #[structopt(short, long, default_value = "text", global = true)]
+    pub format: OutputFormat,
 }
 
-pub fn get_document_name(abb: &str) -> &str {
-    match abb {
-        "AR" => "Annual report",
-        "FR" => "Financial report",
-        "SR" => "Sustainability report",
-        "CG" => "Corporate Governance",
-        "RS" => "Annual Results",
-        "CR" => "Compensation Report",
-        "ST" => "Strategy Report",
-        "AD" => "Addendum",
-        "AM" => "Annual Meeting Minutes",
-        "RR" => "Risk Report",
-        "RV" => "Review",
-        "PS" => "Proxy Statement",
-        "10K" => "SEC Form 10-K",
-        "20F" => "SEC Form 20-F",
-        "GRI" => "GRI Sustainability Reporting Standard",
-        _ => abb,
+impl Configuration {
+    pub fn get_metadata_directory(&self) -> &Path {
+        Path::new(&self.metadata_directory)
+    }
+
+    pub fn get_store_directory(&self) -> PathBuf {
+        Path::new(&self.download_directory).join("store")
     }
-}
 
-pub fn get_language(language: &str) -> &str {
-    match language {
-        "EN" => "English",
-        "DE" => "Deutsch",
-        "FR" => "Français",
-        "IT" => "Italiano",
-        _ => "",
+    pub fn get_retry_policy(&self) -> RetryPolicy {
+        RetryPolicy {
+            max_retries: self.max_retries,
+            initial_backoff: Duration::from_millis(self.initial_backoff_ms),
+            max_backoff: Duration::from_millis(self.max_backoff_ms),
+        }
+    }
+
+    pub fn get_filter(&self) -> error::Result<ReportFilter> {
+        let company = match &self.company {
+            Some(pattern) => Some(RegexBuilder::new(pattern).case_insensitive(true).build()?),
+            None => None,
+        };
+        Ok(ReportFilter {
+            company,
+            tags: self.tag.clone(),
+            from_year: self.from_year,
+            to_year: self.to_year,
+            report_types: self.report_type.clone(),
+            languages: self.language.clone(),
+        })
     }
 }
 
-#[derive(Debug, Deserialize)]
-pub enum Language {
-    EN,
-    DE,
-    FR,
-    IT,
+#[derive(Debug, Default)]
+pub struct ReportFilter {
+    pub company: Option<Regex>,
+    pub tags: Vec<String>,
+    pub from_year: Option<u16>,
+    pub to_year: Option<u16>,
+    pub report_types: Vec<ReportType>,
+    pub languages: Vec<Language>,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+impl ReportFilter {
+    pub fn matches_company(&self, metadata: &CompanyMetadata) -> bool {
+        let name_matches = match &self.company {
+            Some(regex) => regex.is_match(&metadata.name),
+            None => true,
+        };
+        let tag_matches =
+            self.tags.is_empty() || self.tags.iter().any(|t| metadata.tags.contains(t));
+        name_matches && tag_matches
+    }
+
+    pub fn matches_report(&self, report: &Report) -> bool {
+        self.from_year.is_none_or(|year| report.year >= year)
+            && self.to_year.is_none_or(|year| report.year <= year)
+            && (self.report_types.is_empty() || self.report_types.contains(&report.report_type))
+            && (self.languages.is_empty() || self.languages.contains(&report.language))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Report {
     pub company: String,
-    pub language: String,
-    pub report_type: String,
+    pub language: Language,
+    pub report_type: ReportType,
     pub year: u16,
     pub link: String,
+    #[serde(
+        default,
+        deserialize_with = "deserialize_links",
+        serialize_with = "serialize_links"
+    )]
+    pub alternative_links: Vec<String>,
+}
+
+// Alternative links are stored as a single column separated by '|'
+fn deserialize_links<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let links: Option<String> = Option::deserialize(deserializer)?;
+    Ok(links
+        .unwrap_or_default()
+        .split('|')
+        .map(|l| l.trim())
+        .filter(|l| !l.is_empty())
+        .map(|l| l.to_owned())
+        .collect())
+}
+
+fn serialize_links<S>(links: &[String], serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_str(&links.join("|"))
 }
 
 impl Report {
+    pub fn get_links(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.link.as_str()).chain(self.alternative_links.iter().map(|l| l.as_str()))
+    }
+
     pub fn get_file_path(&self, root_path: &Path) -> PathBuf {
-        let file_name = format!("{}-{}.pdf", self.report_type, self.language);
+        let file_name = format!("{}-{}.pdf", self.report_type.code(), self.language.code());
 
         let path = root_path.join(&self.company);
-        let path = path.join(&self.year.to_string());
+        let path = path.join(self.year.to_string());
         //fs::create_dir_all(&path).unwrap();
         path.join(file_name)
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
 pub struct CompanyMetadata {
     pub name: String,
     pub country: String,
@@ -88,32 +248,83 @@ pub struct CompanyMetadata {
     pub url: String,
     pub email: String,
     pub share_class: String,
+    /// Fields that were filled with a default by init-metadata and still need to be checked
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub unverified: Vec<String>,
 }
 
 impl CompanyMetadata {
+    /// Metadata of a company without metadata file, only the name is known
     pub fn new(name: &str) -> CompanyMetadata {
+        CompanyMetadata {
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Metadata file content for a new company, the guessed fields are marked as unverified
+    pub fn stub(name: &str) -> CompanyMetadata {
         CompanyMetadata {
             name: name.to_string(),
             country: "CH".to_string(),
-            tags: vec![],
-            comment: "".to_string(),
-            links: vec![],
             annual_closing_date: "31.12".to_string(),
             accounting_rules: "IFRS".to_string(),
             legal_form: "AG".to_string(),
-            url: "".to_string(),
-            email: "".to_string(),
             share_class: "RS".to_string(),
+            unverified: [
+                "country",
+                "annual_closing_date",
+                "accounting_rules",
+                "legal_form",
+                "share_class",
+            ]
+            .iter()
+            .map(|f| f.to_string())
+            .collect(),
+            ..Default::default()
+        }
+    }
+
+    /// The end of the financial year that ends in the given year, from a closing date like
+    /// 31.03. Without a valid closing date the financial year is the calendar year.
+    pub fn get_closing_date(&self, year: u16) -> NaiveDate {
+        let mut parts = self.annual_closing_date.trim().split('.');
+        let day = parts.next().and_then(|d| d.parse().ok());
+        let month = parts.next().and_then(|m| m.parse().ok());
+        day.zip(month)
+            .and_then(|(day, month)| NaiveDate::from_ymd_opt(year.into(), month, day))
+            .or_else(|| NaiveDate::from_ymd_opt(year.into(), 12, 31))
+            .unwrap()
+    }
+
+    pub fn get_path(directory: &Path, name: &str) -> PathBuf {
+        directory.join(format!("{}.json", name))
+    }
+
+    /// Loads the metadata file of the company or returns metadata with only the name if there
+    /// is none
+    pub fn from_company_name(directory: &Path, name: &str) -> error::Result<CompanyMetadata> {
+        let path = CompanyMetadata::get_path(directory, name);
+        if path.exists() {
+            CompanyMetadata::load(&path)
+        } else {
+            Ok(CompanyMetadata::new(name))
         }
     }
 
-    pub fn from_metadata(filename: &str) -> CompanyMetadata {
-        let contents =
-            &fs::read(&filename).unwrap_or_else(|_| panic!("Reading file {} failed", &filename));
-        let metadata_json: String = String::from_utf8_lossy(contents)
-            .parse()
-            .expect("failed converting to string");
-        serde_json::from_str(&metadata_json).unwrap()
+    pub fn load(path: &Path) -> error::Result<CompanyMetadata> {
+        let contents = fs::read_to_string(path).map_err(Error::io(path))?;
+        serde_json::from_str(&contents).map_err(Error::json(path))
+    }
+
+    pub fn write(&self, directory: &Path) -> error::Result<()> {
+        let path = CompanyMetadata::get_path(directory, &self.name);
+        let serialized = serde_json::to_string_pretty(self).map_err(Error::json(&path))?;
+        fs::write(&path, serialized).map_err(Error::io(&path))
+    }
+
+    pub fn is_verified(&self, field: &str) -> bool {
+        !self.unverified.iter().any(|f| f == field)
     }
 
     pub fn get_domainname(&self, remove_www: bool) -> String {
@@ -126,21 +337,27 @@ impl CompanyMetadata {
     }
 }
 
-pub fn get_metadata<F>(path: &str, f: F) -> Vec<CompanyMetadata>
+/// Loads the metadata files of the directory that match the filter. Files that cannot be loaded
+/// are returned separately so that the caller can report them and continue with the others
+pub fn get_metadata<F>(directory: &Path, f: F) -> error::Result<(Vec<CompanyMetadata>, Vec<Error>)>
 where
     F: Fn(&CompanyMetadata) -> bool,
 {
-    let paths = fs::read_dir(path).unwrap();
     let mut metas = Vec::new();
-    for source_file in paths {
-        let path = source_file.unwrap().path();
-        let meta = CompanyMetadata::from_metadata(path.to_str().unwrap());
-        if f(&meta) {
-            metas.push(meta);
+    let mut errors = Vec::new();
+    for source_file in fs::read_dir(directory).map_err(Error::io(directory))? {
+        let path = source_file.map_err(Error::io(directory))?.path();
+        if path.extension() != Some("json".as_ref()) {
+            continue;
+        }
+        match CompanyMetadata::load(&path) {
+            Ok(meta) if f(&meta) => metas.push(meta),
+            Ok(_) => {}
+            Err(e) => errors.push(e),
         }
     }
     metas.sort();
-    metas
+    Ok((metas, errors))
 }
 
 #[derive(Debug, Deserialize)]
@@ -152,25 +369,12 @@ pub struct Company {
 }
 
 impl Company {
-    pub fn new(reports: Vec<Report>) -> Company {
-        let name = if !reports.is_empty() {
-            reports[0].company.to_owned()
-        } else {
-            String::new()
-        };
+    pub fn new(metadata: CompanyMetadata, reports: Vec<Report>) -> Company {
         let newest_year = reports.iter().fold(0, |acc, x| std::cmp::max(acc, x.year));
         let oldest_year = reports
             .iter()
             .fold(u16::MAX, |acc, x| std::cmp::min(acc, x.year));
 
-        let filename = format!("metadata/{}.json", &name);
-        let metadata = if Path::new(&filename).exists() {
-            CompanyMetadata::from_metadata(&filename)
-        } else {
-            CompanyMetadata::new(&name)
-        };
-        write_metadata(&metadata);
-
         Company {
             metadata,
             reports,
@@ -180,16 +384,86 @@ impl Company {
     }
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Download {
     pub report: Report,
     pub size: u64,
     pub mime_type: String,
+    pub pages: u32,
+    pub validation: Validation,
+    #[serde(default)]
+    pub metadata: Option<PdfMetadata>,
+    pub sha256: String,
+    #[serde(default)]
+    pub source_link: String,
 }
 
 impl Download {
+    pub fn get_source_link(&self) -> &str {
+        if self.source_link.is_empty() {
+            &self.report.link
+        } else {
+            &self.source_link
+        }
+    }
+
+    pub fn is_from_fallback(&self) -> bool {
+        !self.source_link.is_empty() && self.source_link != self.report.link
+    }
+
+    // A report is published after the end of its financial year, a document created before
+    // that is probably the report of an earlier year
+    pub fn is_created_too_early(&self, company: &CompanyMetadata) -> bool {
+        match self.metadata.as_ref().and_then(|m| m.get_creation_day()) {
+            Some(creation_day) => creation_day <= company.get_closing_date(self.report.year),
+            None => false,
+        }
+    }
+
     pub fn has_warning(&self) -> bool {
-        self.mime_type != "application/pdf" || self.size < 10
+        self.get_warning().is_some()
+    }
+
+    pub fn get_warning(&self) -> Option<String> {
+        if !self.validation.is_valid() {
+            Some(self.validation.to_string())
+        } else if self.size < 10 {
+            Some("file too small".to_owned())
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DownloadFailure {
+    pub report: Report,
+    pub error: String,
+    pub status: Option<u16>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct RunManifest {
+    pub date: String,
+    pub downloads: Vec<Download>,
+    pub failures: Vec<DownloadFailure>,
+}
+
+impl RunManifest {
+    pub fn get_path(run_directory: &Path) -> PathBuf {
+        run_directory.join("manifest.json")
+    }
+
+    pub fn load(run_directory: &Path) -> error::Result<RunManifest> {
+        let path = RunManifest::get_path(run_directory);
+        let contents = fs::read_to_string(&path).map_err(Error::io(&path))?;
+        serde_json::from_str(&contents).map_err(Error::json(&path))
+    }
+
+    pub fn write(&self, run_directory: &Path) -> error::Result<()> {
+        let path = RunManifest::get_path(run_directory);
+        let serialized = serde_json::to_string_pretty(self).map_err(Error::json(&path))?;
+        fs::write(&path, serialized).map_err(Error::io(&path))
     }
 }
 
@@ -203,11 +477,11 @@ impl CompanyDownloads {
         self.downloads.iter().filter(|&d| d.has_warning()).count()
     }
 
-    pub fn get_reports(&self, year: u16, language: &str) -> Vec<&Download> {
+    pub fn get_reports(&self, year: u16, language: &Language) -> Vec<&Download> {
         let iter = self
             .downloads
             .iter()
-            .filter(|d| d.report.year == year && d.report.language == language);
+            .filter(|d| d.report.year == year && d.report.language == *language);
         iter.collect()
     }
 }
@@ -221,3 +495,114 @@ pub fn filter_companies<'a>(
         .filter(|c| c.company.metadata.tags.iter().any(|e| e == tag))
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_company(annual_closing_date: &str) -> CompanyMetadata {
+        CompanyMetadata {
+            annual_closing_date: annual_closing_date.to_owned(),
+            ..CompanyMetadata::new("Example")
+        }
+    }
+
+    fn create_download(creation_date: &str) -> Download {
+        Download {
+            report: Report {
+                company: "Example".to_owned(),
+                language: Language::EN,
+                report_type: ReportType::AnnualReport,
+                year: 2023,
+                link: "https://example.ch/ar.pdf".to_owned(),
+                alternative_links: Vec::new(),
+            },
+            size: 1000,
+            mime_type: "application/pdf".to_owned(),
+            pages: 100,
+            validation: Validation::Pdf,
+            metadata: Some(PdfMetadata {
+                creation_date: Some(creation_date.to_owned()),
+                ..Default::default()
+            }),
+            sha256: String::new(),
+            source_link: String::new(),
+        }
+    }
+
+    #[test]
+    fn closing_dates_are_parsed() {
+        let date = |closing: &str| create_company(closing).get_closing_date(2023);
+        assert_eq!(date("31.03"), NaiveDate::from_ymd_opt(2023, 3, 31).unwrap());
+        assert_eq!(date("30.06"), NaiveDate::from_ymd_opt(2023, 6, 30).unwrap());
+        // Unknown or invalid closing dates mean the calendar year
+        assert_eq!(date(""), NaiveDate::from_ymd_opt(2023, 12, 31).unwrap());
+        assert_eq!(
+            date("29.02"),
+            NaiveDate::from_ymd_opt(2023, 12, 31).unwrap()
+        );
+    }
+
+    #[test]
+    fn documents_created_before_the_end_of_the_financial_year_are_too_early() {
+        let download = create_download("2023-06-15T10:00:00Z");
+        assert!(download.is_created_too_early(&create_company("31.12")));
+        assert!(!download.is_created_too_early(&create_company("31.03")));
+        assert!(download.is_created_too_early(&create_company("30.06")));
+        let download = create_download("2024-03-01T10:00:00Z");
+        assert!(!download.is_created_too_early(&create_company("31.12")));
+        let download = Download {
+            metadata: None,
+            ..download
+        };
+        assert!(!download.is_created_too_early(&create_company("31.12")));
+    }
+
+    fn create_filter(args: &[&str]) -> ReportFilter {
+        let args = std::iter::once("test").chain(args.iter().copied());
+        Configuration::from_iter(args).get_filter().unwrap()
+    }
+
+    fn create_tagged_company(name: &str, tags: &[&str]) -> CompanyMetadata {
+        CompanyMetadata {
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            ..CompanyMetadata::new(name)
+        }
+    }
+
+    #[test]
+    fn reports_are_filtered_by_year_range_type_and_language() {
+        let report = create_download("2024-03-01T10:00:00Z").report;
+        assert!(create_filter(&[]).matches_report(&report));
+        // Both ends of the year range are included
+        assert!(
+            create_filter(&["--from-year", "2023", "--to-year", "2023"]).matches_report(&report)
+        );
+        assert!(!create_filter(&["--from-year", "2024"]).matches_report(&report));
+        assert!(!create_filter(&["--to-year", "2022"]).matches_report(&report));
+        assert!(
+            create_filter(&["--report-type", "SR", "--report-type", "AR"]).matches_report(&report)
+        );
+        assert!(!create_filter(&["--report-type", "SR"]).matches_report(&report));
+        assert!(create_filter(&["--language", "en"]).matches_report(&report));
+        assert!(
+            !create_filter(&["--language", "DE", "--from-year", "2020"]).matches_report(&report)
+        );
+    }
+
+    #[test]
+    fn companies_are_filtered_by_name_and_tag() {
+        let company = create_tagged_company("Zürcher Kantonalbank", &["Kantonalbank", "SMIM"]);
+        assert!(create_filter(&[]).matches_company(&company));
+        // The name is a case insensitive regular expression, tags have to match exactly
+        assert!(create_filter(&["--company", "KANTONALBANK"]).matches_company(&company));
+        assert!(create_filter(&["--company", "^zürcher"]).matches_company(&company));
+        assert!(!create_filter(&["--company", "^kantonalbank"]).matches_company(&company));
+        assert!(create_filter(&["--tag", "SMI", "--tag", "SMIM"]).matches_company(&company));
+        assert!(!create_filter(&["--tag", "smim"]).matches_company(&company));
+        // Name and tags have to match both
+        assert!(create_filter(&["--company", "zürcher", "--tag", "SMIM"]).matches_company(&company));
+        assert!(!create_filter(&["--company", "zürcher", "--tag", "SMI"]).matches_company(&company));
+        assert!(!create_filter(&["--company", "bern", "--tag", "SMIM"]).matches_company(&company));
+    }
+}
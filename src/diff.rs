@@ -0,0 +1,270 @@
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use crate::data_structures::{Download, Language, Report, ReportType, RunManifest};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ChangeKind {
+    Unchanged,
+    ContentChanged,
+    SizeChanged,
+    New,
+    /// The server no longer has the document (404 or 410)
+    Disappeared,
+    /// The download failed for another reason, e.g. a timeout or a server error
+    Unavailable,
+}
+
+impl fmt::Display for ChangeKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ChangeKind::Unchanged => write!(f, "Unchanged"),
+            ChangeKind::ContentChanged => write!(f, "Content changed"),
+            ChangeKind::SizeChanged => write!(f, "Size or page count changed"),
+            ChangeKind::New => write!(f, "Newly available"),
+            ChangeKind::Disappeared => write!(f, "Disappeared"),
+            ChangeKind::Unavailable => write!(f, "Temporarily unavailable"),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Change {
+    pub company: String,
+    pub year: u16,
+    pub report_type: ReportType,
+    pub language: Language,
+    pub link: String,
+    pub kind: ChangeKind,
+    pub old_sha256: Option<String>,
+    pub new_sha256: Option<String>,
+    pub old_size: Option<u64>,
+    pub new_size: Option<u64>,
+    pub old_pages: Option<u32>,
+    pub new_pages: Option<u32>,
+    pub details: String,
+}
+
+impl Change {
+    fn new(
+        report: &Report,
+        kind: ChangeKind,
+        old: Option<&Download>,
+        new: Option<&Download>,
+    ) -> Change {
+        Change {
+            company: report.company.clone(),
+            year: report.year,
+            report_type: report.report_type.clone(),
+            language: report.language.clone(),
+            link: report.link.clone(),
+            kind,
+            old_sha256: old.map(|d| d.sha256.clone()),
+            new_sha256: new.map(|d| d.sha256.clone()),
+            old_size: old.map(|d| d.size),
+            new_size: new.map(|d| d.size),
+            old_pages: old.map(|d| d.pages),
+            new_pages: new.map(|d| d.pages),
+            details: String::new(),
+        }
+    }
+}
+
+type ReportKey = (String, u16, ReportType, Language);
+
+fn get_key(report: &Report) -> ReportKey {
+    (
+        report.company.clone(),
+        report.year,
+        report.report_type.clone(),
+        report.language.clone(),
+    )
+}
+
+// Reports that the new run did not attempt at all (e.g. because of a filter) are not reported
+pub fn compare_runs(old: &RunManifest, new: &RunManifest) -> Vec<Change> {
+    let old_downloads: BTreeMap<ReportKey, &Download> = old
+        .downloads
+        .iter()
+        .map(|d| (get_key(&d.report), d))
+        .collect();
+    let mut changes = Vec::new();
+
+    for new_download in &new.downloads {
+        let change = match old_downloads.get(&get_key(&new_download.report)) {
+            None => Change::new(
+                &new_download.report,
+                ChangeKind::New,
+                None,
+                Some(new_download),
+            ),
+            Some(old_download) => {
+                let kind = if old_download.sha256 == new_download.sha256 {
+                    ChangeKind::Unchanged
+                } else if old_download.size != new_download.size
+                    || old_download.pages != new_download.pages
+                {
+                    ChangeKind::SizeChanged
+                } else {
+                    ChangeKind::ContentChanged
+                };
+                let mut change = Change::new(
+                    &new_download.report,
+                    kind,
+                    Some(old_download),
+                    Some(new_download),
+                );
+                if kind == ChangeKind::SizeChanged {
+                    change.details = format!(
+                        "{} kB -> {} kB, {} pages -> {} pages",
+                        old_download.size,
+                        new_download.size,
+                        old_download.pages,
+                        new_download.pages
+                    );
+                }
+                change
+            }
+        };
+        changes.push(change);
+    }
+    for failure in &new.failures {
+        if let Some(old_download) = old_downloads.get(&get_key(&failure.report)) {
+            let kind = match failure.status {
+                Some(404) | Some(410) => ChangeKind::Disappeared,
+                _ => ChangeKind::Unavailable,
+            };
+            let mut change = Change::new(&failure.report, kind, Some(old_download), None);
+            change.details = failure.error.clone();
+            changes.push(change);
+        }
+    }
+    changes.sort_by(|a, b| {
+        (
+            &a.company,
+            std::cmp::Reverse(a.year),
+            &a.report_type,
+            &a.language,
+        )
+            .cmp(&(
+                &b.company,
+                std::cmp::Reverse(b.year),
+                &b.report_type,
+                &b.language,
+            ))
+    });
+    changes
+}
+
+pub fn write_changes_json(path: &Path, changes: &[Change]) -> Result<(), Box<dyn Error>> {
+    fs::write(path, serde_json::to_string_pretty(changes)?)?;
+    Ok(())
+}
+
+pub fn write_changes_csv(path: &Path, changes: &[Change]) -> Result<(), Box<dyn Error>> {
+    let mut writer = csv::WriterBuilder::new().delimiter(b';').from_path(path)?;
+    for change in changes {
+        writer.serialize(change)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_structures::DownloadFailure;
+    use crate::validation::Validation;
+
+    fn create_report(company: &str, year: u16) -> Report {
+        Report {
+            company: company.to_owned(),
+            language: Language::EN,
+            report_type: ReportType::AnnualReport,
+            year,
+            link: format!("https://example.ch/{}.pdf", year),
+            alternative_links: Vec::new(),
+        }
+    }
+
+    fn create_download(company: &str, year: u16, sha256: &str, size: u64) -> Download {
+        Download {
+            report: create_report(company, year),
+            size,
+            mime_type: "application/pdf".to_owned(),
+            pages: 100,
+            validation: Validation::Pdf,
+            metadata: None,
+            sha256: sha256.to_owned(),
+            source_link: String::new(),
+        }
+    }
+
+    fn create_failure(company: &str, year: u16, status: Option<u16>) -> DownloadFailure {
+        DownloadFailure {
+            report: create_report(company, year),
+            error: "HTTP error".to_owned(),
+            status,
+        }
+    }
+
+    #[test]
+    fn added_changed_and_removed_documents_are_found() {
+        let old = RunManifest {
+            downloads: vec![
+                create_download("A", 2021, "a", 1000),
+                create_download("A", 2022, "b", 1000),
+                create_download("A", 2023, "c", 1000),
+                create_download("B", 2021, "d", 1000),
+                create_download("B", 2022, "e", 1000),
+                create_download("C", 2022, "f", 1000),
+            ],
+            ..Default::default()
+        };
+        let new = RunManifest {
+            downloads: vec![
+                create_download("A", 2021, "a", 1000),
+                create_download("A", 2022, "x", 1000),
+                create_download("A", 2023, "y", 2000),
+                create_download("A", 2024, "z", 1000),
+            ],
+            failures: vec![
+                create_failure("B", 2021, Some(404)),
+                create_failure("B", 2022, None),
+                // Failures without an earlier download are no change
+                create_failure("B", 2023, Some(410)),
+            ],
+            ..Default::default()
+        };
+        let changes = compare_runs(&old, &new);
+        let kinds: Vec<_> = changes
+            .iter()
+            .map(|c| (c.company.as_str(), c.year, c.kind))
+            .collect();
+        // Documents of C were not attempted by the new run and are left out
+        assert_eq!(
+            kinds,
+            vec![
+                ("A", 2024, ChangeKind::New),
+                ("A", 2023, ChangeKind::SizeChanged),
+                ("A", 2022, ChangeKind::ContentChanged),
+                ("A", 2021, ChangeKind::Unchanged),
+                ("B", 2022, ChangeKind::Unavailable),
+                ("B", 2021, ChangeKind::Disappeared),
+            ]
+        );
+        assert_eq!(changes[0].old_sha256, None);
+        assert_eq!(changes[0].new_sha256.as_deref(), Some("z"));
+        assert_eq!(
+            changes[1].details,
+            "1000 kB -> 2000 kB, 100 pages -> 100 pages"
+        );
+        assert_eq!(changes[5].old_sha256.as_deref(), Some("d"));
+        assert_eq!(changes[5].new_sha256, None);
+        assert_eq!(changes[5].details, "HTTP error");
+    }
+}
@@ -0,0 +1,453 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use log::{debug, error, info, warn};
+use rand::Rng;
+use reqwest::header::{HeaderMap, CONTENT_RANGE, ETAG, IF_RANGE, LAST_MODIFIED, RANGE};
+use reqwest::{Client, StatusCode, Url};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::{Mutex, Semaphore};
+
+use crate::data_structures::{Configuration, Download, Report, RunManifest};
+use crate::link_check::LinkStatus;
+use crate::store::DocumentStore;
+use crate::validation;
+
+pub type DownloadError = Box<dyn Error + Send + Sync>;
+
+#[derive(Debug)]
+pub struct HttpStatusError(pub StatusCode);
+
+impl fmt::Display for HttpStatusError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "HTTP status {}", self.0)
+    }
+}
+
+impl Error for HttpStatusError {}
+
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl RetryPolicy {
+    pub fn get_backoff(&self, attempt: u32) -> Duration {
+        let backoff = self
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_backoff);
+        // Use between half and the full backoff so parallel downloads do not retry in lockstep
+        let half = backoff / 2;
+        let jitter = rand::thread_rng().gen_range(0..=half.as_millis() as u64);
+        half + Duration::from_millis(jitter)
+    }
+}
+
+enum AttemptError {
+    Retryable(DownloadError),
+    Fatal(DownloadError),
+}
+
+fn get_part_path(file_path: &Path) -> PathBuf {
+    let mut part_path = file_path.as_os_str().to_owned();
+    part_path.push(".part");
+    PathBuf::from(part_path)
+}
+
+// The ETag or Last-Modified date of the document a partial file belongs to
+fn get_validator_path(part_path: &Path) -> PathBuf {
+    let mut validator_path = part_path.as_os_str().to_owned();
+    validator_path.push(".validator");
+    PathBuf::from(validator_path)
+}
+
+fn remove_if_exists(path: &Path) -> std::io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+// Removes a partial download so that the next attempt starts from the beginning
+fn remove_partial(part_path: &Path) -> std::io::Result<()> {
+    remove_if_exists(part_path)?;
+    remove_if_exists(&get_validator_path(part_path))
+}
+
+// Weak ETags cannot be used for range requests, the modification date is used instead
+fn get_validator(headers: &HeaderMap) -> Option<String> {
+    let etag = headers
+        .get(ETAG)
+        .and_then(|v| v.to_str().ok())
+        .filter(|etag| !etag.starts_with("W/"));
+    etag.or_else(|| headers.get(LAST_MODIFIED).and_then(|v| v.to_str().ok()))
+        .map(|v| v.to_owned())
+}
+
+// The first byte of a partial response, e.g. 100 for "bytes 100-999/1000"
+fn get_range_start(headers: &HeaderMap) -> Option<u64> {
+    let range = headers.get(CONTENT_RANGE)?.to_str().ok()?;
+    range
+        .strip_prefix("bytes ")?
+        .split('-')
+        .next()?
+        .parse()
+        .ok()
+}
+
+// A stalled transfer never fails on its own, so every read is limited
+async fn with_read_timeout<T>(
+    read_timeout: Duration,
+    future: impl Future<Output = reqwest::Result<T>>,
+) -> Result<T, AttemptError> {
+    match tokio::time::timeout(read_timeout, future).await {
+        Ok(result) => result.map_err(|e| AttemptError::Retryable(e.into())),
+        Err(_) => Err(AttemptError::Retryable(
+            format!("no data received for {:?}", read_timeout).into(),
+        )),
+    }
+}
+
+async fn download_attempt(
+    link: &str,
+    part_path: &Path,
+    client: &Client,
+    read_timeout: Duration,
+) -> Result<(), AttemptError> {
+    let validator_path = get_validator_path(part_path);
+    let offset = fs::metadata(part_path).map(|m| m.len()).unwrap_or(0);
+    let validator = fs::read_to_string(&validator_path).ok();
+    let mut request = client.get(link);
+    // Without a validator a changed document could not be detected, so it is downloaded again
+    if let (true, Some(validator)) = (offset > 0, &validator) {
+        request = request
+            .header(RANGE, format!("bytes={}-", offset))
+            .header(IF_RANGE, validator.as_str());
+    }
+    let mut response = with_read_timeout(read_timeout, request.send()).await?;
+
+    let status = response.status();
+    let append = match status {
+        StatusCode::PARTIAL_CONTENT => {
+            if validator.is_none() || get_range_start(response.headers()) != Some(offset) {
+                remove_partial(part_path).map_err(|e| AttemptError::Fatal(e.into()))?;
+                return Err(AttemptError::Retryable(
+                    format!("unexpected partial content for offset {}", offset).into(),
+                ));
+            }
+            true
+        }
+        StatusCode::RANGE_NOT_SATISFIABLE => {
+            // The partial file does not match the remote file anymore, start over
+            remove_partial(part_path).map_err(|e| AttemptError::Fatal(e.into()))?;
+            return Err(AttemptError::Retryable(
+                format!("range starting at {} not satisfiable", offset).into(),
+            ));
+        }
+        s if s.is_success() => false,
+        s if s.is_server_error() || s == StatusCode::TOO_MANY_REQUESTS => {
+            return Err(AttemptError::Retryable(HttpStatusError(s).into()))
+        }
+        s => return Err(AttemptError::Fatal(HttpStatusError(s).into())),
+    };
+    if offset > 0 {
+        if append {
+            debug!("Resuming {:?} at byte {}", part_path, offset);
+        } else {
+            debug!(
+                "Document changed or range not supported, restarting {:?}",
+                part_path
+            );
+        }
+    }
+    if !append {
+        let result = match get_validator(response.headers()) {
+            Some(validator) => fs::write(&validator_path, validator),
+            None => remove_if_exists(&validator_path),
+        };
+        result.map_err(|e| AttemptError::Fatal(e.into()))?;
+    }
+
+    let mut file = tokio::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .append(append)
+        .truncate(!append)
+        .open(part_path)
+        .await
+        .map_err(|e| AttemptError::Fatal(e.into()))?;
+
+    while let Some(chunk) = with_read_timeout(read_timeout, response.chunk()).await? {
+        file.write_all(&chunk)
+            .await
+            .map_err(|e| AttemptError::Fatal(e.into()))?;
+    }
+    file.flush()
+        .await
+        .map_err(|e| AttemptError::Fatal(e.into()))?;
+    Ok(())
+}
+
+struct HostLimit {
+    semaphore: Semaphore,
+    last_request: Mutex<Option<Instant>>,
+}
+
+pub struct Downloader {
+    client: Client,
+    policy: RetryPolicy,
+    downloads: Semaphore,
+    hosts: std::sync::Mutex<HashMap<String, Arc<HostLimit>>>,
+    max_per_host: usize,
+    host_delay: Duration,
+    read_timeout: Duration,
+    store: DocumentStore,
+    /// Links the files of an earlier execution of the same run were downloaded from
+    previous_sources: HashMap<PathBuf, String>,
+}
+
+impl Downloader {
+    pub fn new(c: &Configuration) -> reqwest::Result<Downloader> {
+        let client = Client::builder()
+            .user_agent(&c.user_agent)
+            .connect_timeout(Duration::from_secs(c.connect_timeout_secs))
+            .build()?;
+        Ok(Downloader {
+            client,
+            policy: c.get_retry_policy(),
+            downloads: Semaphore::new(c.max_concurrent_downloads.max(1)),
+            hosts: std::sync::Mutex::new(HashMap::new()),
+            max_per_host: c.max_downloads_per_host.max(1),
+            host_delay: Duration::from_millis(c.host_delay_ms),
+            read_timeout: Duration::from_secs(c.read_timeout_secs),
+            store: DocumentStore::new(&c.get_store_directory()),
+            previous_sources: HashMap::new(),
+        })
+    }
+
+    /// Keeps the files that an earlier execution of the run already downloaded, together with
+    /// the links they came from
+    pub fn set_previous_run(&mut self, root_path: &Path, manifest: &RunManifest) {
+        self.previous_sources = manifest
+            .downloads
+            .iter()
+            .map(|d| {
+                let file_path = d.report.get_file_path(root_path);
+                (file_path, d.get_source_link().to_owned())
+            })
+            .collect();
+    }
+
+    fn get_host_limit(&self, link: &str) -> Arc<HostLimit> {
+        let host = Url::parse(link)
+            .ok()
+            .and_then(|url| url.host_str().map(|h| h.to_owned()))
+            .unwrap_or_default();
+        let mut hosts = self.hosts.lock().unwrap();
+        hosts
+            .entry(host)
+            .or_insert_with(|| {
+                Arc::new(HostLimit {
+                    semaphore: Semaphore::new(self.max_per_host),
+                    last_request: Mutex::new(None),
+                })
+            })
+            .clone()
+    }
+
+    async fn wait_for_host(&self, host: &HostLimit) {
+        let mut last_request = host.last_request.lock().await;
+        if let Some(last) = *last_request {
+            let elapsed = last.elapsed();
+            if elapsed < self.host_delay {
+                tokio::time::sleep(self.host_delay - elapsed).await;
+            }
+        }
+        *last_request = Some(Instant::now());
+    }
+
+    pub async fn reqwest_download(
+        &self,
+        link: &str,
+        file_path: &Path,
+    ) -> Result<(), DownloadError> {
+        let host = self.get_host_limit(link);
+        let part_path = get_part_path(file_path);
+        let mut attempt = 0;
+        loop {
+            // The permits are only held during an attempt so that the backoff of a flaky host
+            // does not block other downloads. The global permit is taken last, downloads
+            // waiting for a busy host must not keep other hosts from being downloaded.
+            let result = {
+                let _host_permit = host.semaphore.acquire().await?;
+                self.wait_for_host(&host).await;
+                let _permit = self.downloads.acquire().await?;
+                download_attempt(link, &part_path, &self.client, self.read_timeout).await
+            };
+            match result {
+                Ok(()) => {
+                    fs::rename(&part_path, file_path)?;
+                    remove_partial(&part_path)?;
+                    return Ok(());
+                }
+                Err(AttemptError::Retryable(e)) if attempt < self.policy.max_retries => {
+                    let backoff = self.policy.get_backoff(attempt);
+                    attempt += 1;
+                    warn!(
+                        "Attempt {} for {} failed ({}), retrying in {:?}",
+                        attempt, link, e, backoff
+                    );
+                    tokio::time::sleep(backoff).await;
+                }
+                Err(AttemptError::Retryable(e)) | Err(AttemptError::Fatal(e)) => return Err(e),
+            }
+        }
+    }
+
+    pub async fn check_link(&self, report: Report) -> Result<LinkStatus, DownloadError> {
+        let host = self.get_host_limit(&report.link);
+        let _host_permit = host.semaphore.acquire().await?;
+        self.wait_for_host(&host).await;
+        let head = {
+            let _permit = self.downloads.acquire().await?;
+            // The responses have no body, so the read timeout limits the whole request
+            self.client
+                .head(&report.link)
+                .timeout(self.read_timeout)
+                .send()
+                .await
+        };
+        let response = match head {
+            Ok(response) if response.status().is_success() => Ok(response),
+            // Some servers do not implement HEAD, ask for the first byte instead
+            _ => {
+                self.wait_for_host(&host).await;
+                let _permit = self.downloads.acquire().await?;
+                self.client
+                    .get(&report.link)
+                    .header(RANGE, "bytes=0-0")
+                    .timeout(self.read_timeout)
+                    .send()
+                    .await
+            }
+        };
+        let status = match response {
+            Ok(response) => LinkStatus::from_response(report, &response),
+            Err(e) => LinkStatus::from_error(report, e.to_string()),
+        };
+        Ok(status)
+    }
+
+    // Tries the primary link and then the alternative links until one of them returns a valid pdf.
+    // If none does, the first document that could be downloaded at all is kept.
+    async fn download_with_fallback(
+        &self,
+        report: &Report,
+        file_path: &Path,
+    ) -> Result<String, DownloadError> {
+        let mut rejected_path = file_path.as_os_str().to_owned();
+        rejected_path.push(".rejected");
+        let rejected_path = PathBuf::from(rejected_path);
+        let mut rejected_link = None;
+        let mut last_error = None;
+
+        let part_path = get_part_path(file_path);
+        for (index, link) in report.get_links().enumerate() {
+            // A partial file of another link must not be resumed from this one
+            if index > 0 {
+                remove_partial(&part_path)?;
+            }
+            if let Err(e) = self.reqwest_download(link, file_path).await {
+                warn!("Downloading {} failed: {}", link, e);
+                last_error = Some(e);
+                continue;
+            }
+            let check = validation::validate_file(file_path);
+            if check.validation.is_valid() {
+                if rejected_link.is_some() {
+                    fs::remove_file(&rejected_path)?;
+                }
+                return Ok(link.to_owned());
+            }
+            warn!(
+                "{} returned an invalid document: {}",
+                link, check.validation
+            );
+            if rejected_link.is_none() {
+                fs::rename(file_path, &rejected_path)?;
+                rejected_link = Some(link.to_owned());
+            } else {
+                fs::remove_file(file_path)?;
+            }
+        }
+        match rejected_link {
+            Some(link) => {
+                fs::rename(&rejected_path, file_path)?;
+                Ok(link)
+            }
+            None => Err(last_error.unwrap_or_else(|| "No link to download".into())),
+        }
+    }
+
+    pub async fn download(
+        &self,
+        root_path: &Path,
+        report: Report,
+    ) -> Result<Download, DownloadError> {
+        let file_path = report.get_file_path(root_path);
+        fs::create_dir_all(file_path.parent().unwrap())?;
+        let previous_source = match self.previous_sources.get(&file_path) {
+            Some(link) if file_path.exists() => Some(link.clone()),
+            // The link of a file left by an aborted execution is not known
+            _ => {
+                remove_if_exists(&file_path)?;
+                None
+            }
+        };
+        let source_link = match previous_source {
+            Some(link) => {
+                debug!("file already exists: '{:?}'", file_path);
+                link
+            }
+            None => {
+                info!("Processing path: '{:?}'", file_path);
+                self.download_with_fallback(&report, &file_path).await?
+            }
+        };
+        if source_link != report.link {
+            info!("Downloaded {:?} from fallback {}", file_path, source_link);
+        }
+        let (sha256, is_new) = self.store.insert(&file_path)?;
+        if is_new {
+            info!("New document content for {:?}: {}", file_path, sha256);
+        } else {
+            debug!("Document {:?} is already stored as {}", file_path, sha256);
+        }
+        let metadata = fs::metadata(&file_path)?;
+        let size = metadata.len() / 1024;
+        let check = validation::validate_file(&file_path);
+        if !check.validation.is_valid() {
+            error!("Invalid file {:?}: {}", file_path, check.validation);
+        }
+        let d = Download {
+            report,
+            size,
+            mime_type: check.mime_type,
+            pages: check.pages,
+            validation: check.validation,
+            metadata: check.metadata,
+            sha256,
+            source_link,
+        };
+        Ok(d)
+    }
+}
@@ -0,0 +1,655 @@
+use chrono::{SecondsFormat, Utc};
+use serde_derive::Serialize;
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{self, Write};
+use viaspf::lookup::{Lookup, LookupError, LookupResult, Name};
+use viaspf_record::{DomainSpec, Mechanism, Modifier, Qualifier, Record};
+
+use crate::checks::{self, SpfStatus};
+use crate::data_structures::CompanyMetadata;
+
+/// Receivers stop the SPF evaluation with a permanent error after more lookups (RFC 7208, 4.6.4)
+pub const MAX_SPF_LOOKUPS: usize = 10;
+
+/// Receivers may fail the evaluation after more lookups without an answer (RFC 7208, 4.6.4)
+pub const MAX_SPF_VOID_LOOKUPS: usize = 2;
+
+/// DKIM selectors cannot be listed, so only the ones used by common mail providers are tried
+pub const DKIM_SELECTORS: &[&str] = &[
+    "default",
+    "selector1",
+    "selector2",
+    "google",
+    "k1",
+    "s1",
+    "s2",
+    "dkim",
+    "mail",
+];
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Grade {
+    A,
+    B,
+    C,
+    D,
+    /// Missing or broken
+    F,
+}
+
+impl Grade {
+    /// One grade lower for a weakness, F stays reserved for missing or broken records
+    fn lower(self) -> Grade {
+        match self {
+            Grade::A => Grade::B,
+            Grade::B => Grade::C,
+            Grade::C | Grade::D => Grade::D,
+            Grade::F => Grade::F,
+        }
+    }
+}
+
+impl fmt::Display for Grade {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Grade::A => "A",
+            Grade::B => "B",
+            Grade::C => "C",
+            Grade::D => "D",
+            Grade::F => "F",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+fn format_grade(grade: Option<Grade>) -> String {
+    grade.map_or_else(|| "-".to_owned(), |g| g.to_string())
+}
+
+/// Grade of one part of the audit, without a grade if the DNS lookup failed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub grade: Option<Grade>,
+    pub details: String,
+}
+
+impl Finding {
+    fn graded(grade: Grade, details: String) -> Finding {
+        Finding {
+            grade: Some(grade),
+            details,
+        }
+    }
+
+    fn failed(details: String) -> Finding {
+        Finding {
+            grade: None,
+            details,
+        }
+    }
+}
+
+/// Value of a tag in the `name=value; ...` syntax of DMARC, DKIM, MTA-STS and TLS-RPT records
+pub fn get_tag<'a>(record: &'a str, name: &str) -> Option<&'a str> {
+    record
+        .split(';')
+        .filter_map(|tag| tag.split_once('='))
+        .find(|(key, _)| key.trim().eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.trim())
+}
+
+/// TXT records of the name that start with the version tag, none if the name does not exist
+async fn get_records<L>(lookup: &L, name: &str, version: &str) -> Result<Vec<String>, String>
+where
+    L: Lookup + ?Sized,
+{
+    match checks::lookup_txt(lookup, name).await {
+        Ok(records) => Ok(records
+            .into_iter()
+            .filter(|r| {
+                r.get(..version.len())
+                    .is_some_and(|v| v.eq_ignore_ascii_case(version))
+            })
+            .collect()),
+        Err(LookupError::NoRecords) => Ok(Vec::new()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SpfEvaluation {
+    /// DNS lookups needed to evaluate the record, including the ones of included records
+    pub lookups: usize,
+    /// Lookups of a, mx and exists mechanisms for names without addresses or exchanges
+    pub void_lookups: usize,
+    /// Qualifier of the all mechanism that ends the evaluation, None if there is none
+    pub all: Option<Qualifier>,
+    pub includes: Vec<String>,
+    /// Errors that make receivers fail the evaluation
+    pub problems: Vec<String>,
+}
+
+// A DNS lookup still to be done during the evaluation
+enum Pending {
+    /// An included or redirected record, with the domains that included it to find loops. Its
+    /// all mechanism decides the policy if it was reached by redirects.
+    Record {
+        domain: String,
+        chain: Vec<String>,
+        decides_policy: bool,
+    },
+    /// The name of an a mechanism, which needs an IPv4 or IPv6 address
+    Address(String),
+    /// The name of an exists mechanism, which needs an IPv4 address
+    Exists(String),
+    /// The name of an mx mechanism
+    Exchange(String),
+}
+
+// Domains with macros depend on the sender and cannot be checked in advance
+fn get_domain(domain_spec: Option<&DomainSpec>, chain: &[String]) -> Option<String> {
+    let domain = match domain_spec {
+        Some(domain_spec) => domain_spec.to_string(),
+        None => chain.last()?.clone(),
+    };
+    if domain.contains('%') {
+        None
+    } else {
+        Some(domain)
+    }
+}
+
+impl SpfEvaluation {
+    fn add_record(
+        &mut self,
+        record: &str,
+        chain: Vec<String>,
+        decides_policy: bool,
+        pending: &mut VecDeque<Pending>,
+    ) {
+        let record = match record.parse::<Record>() {
+            Ok(record) => record,
+            Err(_) => return,
+        };
+        let mut has_all = false;
+        for directive in record.directives() {
+            // Names of the mechanisms that are looked up to find void lookups
+            let name = match &directive.mechanism {
+                Mechanism::All => {
+                    if decides_policy && !has_all {
+                        self.all = Some(directive.qualifier.unwrap_or_default());
+                    }
+                    has_all = true;
+                    continue;
+                }
+                Mechanism::Include(include) => {
+                    self.lookups += 1;
+                    self.follow(include.domain_spec.to_string(), &chain, false, pending);
+                    continue;
+                }
+                Mechanism::A(a) => get_domain(a.domain_spec.as_ref(), &chain).map(Pending::Address),
+                Mechanism::Mx(mx) => {
+                    get_domain(mx.domain_spec.as_ref(), &chain).map(Pending::Exchange)
+                }
+                Mechanism::Exists(exists) => {
+                    get_domain(Some(&exists.domain_spec), &chain).map(Pending::Exists)
+                }
+                // The result of ptr depends on the sender
+                Mechanism::Ptr(_) => None,
+                Mechanism::Ip4(_) | Mechanism::Ip6(_) => continue,
+            };
+            self.lookups += 1;
+            pending.extend(name);
+        }
+        // A redirect is only used if no mechanism matched, so never after all
+        if !has_all {
+            for modifier in record.modifiers() {
+                if let Modifier::Redirect(redirect) = modifier {
+                    self.lookups += 1;
+                    let target = redirect.domain_spec.to_string();
+                    self.follow(target, &chain, decides_policy, pending);
+                }
+            }
+        }
+    }
+
+    fn follow(
+        &mut self,
+        domain: String,
+        chain: &[String],
+        decides_policy: bool,
+        pending: &mut VecDeque<Pending>,
+    ) {
+        if domain.contains('%') {
+            return;
+        }
+        if chain.iter().any(|d| d.eq_ignore_ascii_case(&domain)) {
+            self.problems
+                .push(format!("loop: {} -> {}", chain.join(" -> "), domain));
+            return;
+        }
+        self.includes.push(domain.clone());
+        pending.push_back(Pending::Record {
+            domain,
+            chain: chain.to_vec(),
+            decides_policy,
+        });
+    }
+}
+
+fn is_void<T>(result: LookupResult<Vec<T>>) -> bool {
+    match result {
+        Ok(records) => records.is_empty(),
+        Err(e) => matches!(e, LookupError::NoRecords),
+    }
+}
+
+// Whether the name has none of the records the mechanism needs. Other lookup errors are
+// temporary and no void lookups.
+async fn is_void_lookup<L>(lookup: &L, kind: &Pending) -> bool
+where
+    L: Lookup + ?Sized,
+{
+    let domain = match kind {
+        Pending::Address(domain) | Pending::Exists(domain) | Pending::Exchange(domain) => domain,
+        Pending::Record { .. } => return false,
+    };
+    let name = match Name::new(domain) {
+        Ok(name) => name,
+        Err(_) => return false,
+    };
+    match kind {
+        Pending::Address(_) => {
+            is_void(lookup.lookup_a(&name).await) && is_void(lookup.lookup_aaaa(&name).await)
+        }
+        Pending::Exists(_) => is_void(lookup.lookup_a(&name).await),
+        _ => is_void(lookup.lookup_mx(&name).await),
+    }
+}
+
+/// Follows the includes and redirects of an SPF record and counts the DNS lookups and the void
+/// lookups of its mechanisms
+pub async fn evaluate_spf<L>(lookup: &L, domain: &str, record: &str) -> SpfEvaluation
+where
+    L: Lookup + ?Sized,
+{
+    let mut evaluation = SpfEvaluation::default();
+    let mut pending = VecDeque::new();
+    evaluation.add_record(record, vec![domain.to_owned()], true, &mut pending);
+    while let Some(next) = pending.pop_front() {
+        if evaluation.lookups > MAX_SPF_LOOKUPS {
+            break;
+        }
+        let (domain, chain, decides_policy) = match next {
+            Pending::Record {
+                domain,
+                chain,
+                decides_policy,
+            } => (domain, chain, decides_policy),
+            _ => {
+                if is_void_lookup(lookup, &next).await {
+                    evaluation.void_lookups += 1;
+                }
+                continue;
+            }
+        };
+        // Includes and redirects without a record are already errors on their own
+        let problem = match checks::get_spf_status(lookup, &domain).await {
+            SpfStatus::Valid(record) => {
+                let mut chain = chain;
+                chain.push(domain);
+                evaluation.add_record(&record, chain, decides_policy, &mut pending);
+                continue;
+            }
+            SpfStatus::Invalid(_) => "invalid record".to_owned(),
+            SpfStatus::Multiple(_) => "multiple records".to_owned(),
+            SpfStatus::Missing => "no SPF record".to_owned(),
+            SpfStatus::LookupFailed(e) => e,
+        };
+        evaluation.problems.push(format!("{}: {}", domain, problem));
+    }
+    if evaluation.lookups > MAX_SPF_LOOKUPS {
+        evaluation
+            .problems
+            .push(format!("more than {} DNS lookups", MAX_SPF_LOOKUPS));
+    }
+    if evaluation.void_lookups > MAX_SPF_VOID_LOOKUPS {
+        evaluation.problems.push(format!(
+            "more than {} void DNS lookups",
+            MAX_SPF_VOID_LOOKUPS
+        ));
+    }
+    evaluation
+}
+
+pub async fn audit_spf<L>(lookup: &L, domain: &str) -> Finding
+where
+    L: Lookup + ?Sized,
+{
+    let record = match checks::get_spf_status(lookup, domain).await {
+        SpfStatus::Valid(record) => record,
+        SpfStatus::Invalid(record) => {
+            return Finding::graded(Grade::F, format!("invalid record: {}", record))
+        }
+        SpfStatus::Multiple(_) => return Finding::graded(Grade::F, "multiple records".to_owned()),
+        SpfStatus::Missing => return Finding::graded(Grade::F, "no SPF record".to_owned()),
+        SpfStatus::LookupFailed(e) => return Finding::failed(e),
+    };
+    let evaluation = evaluate_spf(lookup, domain, &record).await;
+    let (grade, policy) = match evaluation.all {
+        Some(Qualifier::Fail) => (Grade::A, "-all"),
+        Some(Qualifier::Softfail) => (Grade::B, "~all"),
+        Some(Qualifier::Neutral) => (Grade::D, "?all"),
+        None => (Grade::D, "no all"),
+        // Every sender passes
+        Some(Qualifier::Pass) => (Grade::F, "+all"),
+    };
+    let mut details = vec![
+        policy.to_owned(),
+        format!("{} DNS lookups", evaluation.lookups),
+    ];
+    details.extend(evaluation.problems.iter().cloned());
+    let grade = if evaluation.problems.is_empty() {
+        grade
+    } else {
+        Grade::F
+    };
+    Finding::graded(grade, details.join(", "))
+}
+
+// Policies from weakest to strongest
+const DMARC_POLICIES: [&str; 3] = ["none", "quarantine", "reject"];
+
+fn get_dmarc_policy(value: Option<&str>) -> Option<usize> {
+    let value = value?;
+    DMARC_POLICIES
+        .iter()
+        .position(|p| p.eq_ignore_ascii_case(value))
+}
+
+pub async fn audit_dmarc<L>(lookup: &L, domain: &str) -> Finding
+where
+    L: Lookup + ?Sized,
+{
+    let records = match get_records(lookup, &format!("_dmarc.{}", domain), "v=DMARC1").await {
+        Ok(records) => records,
+        Err(e) => return Finding::failed(e),
+    };
+    let record = match records.as_slice() {
+        [] => return Finding::graded(Grade::F, "no DMARC record".to_owned()),
+        [record] => record,
+        _ => return Finding::graded(Grade::F, "multiple records".to_owned()),
+    };
+    let policy = match get_dmarc_policy(get_tag(record, "p")) {
+        Some(policy) => policy,
+        None => return Finding::graded(Grade::F, format!("invalid record: {}", record)),
+    };
+    let mut grade = match policy {
+        2 => Grade::A,
+        1 => Grade::B,
+        _ => Grade::D,
+    };
+    let mut details = vec![format!("p={}", DMARC_POLICIES[policy])];
+    if let Some(subdomain_policy) = get_dmarc_policy(get_tag(record, "sp")) {
+        if subdomain_policy < policy {
+            grade = grade.lower();
+            details.push(format!("sp={}", DMARC_POLICIES[subdomain_policy]));
+        }
+    }
+    if let Some(pct) = get_tag(record, "pct").and_then(|pct| pct.parse::<u8>().ok()) {
+        if pct < 100 {
+            grade = grade.lower();
+            details.push(format!("pct={}", pct));
+        }
+    }
+    if get_tag(record, "rua").is_none() {
+        grade = grade.lower();
+        details.push("no rua".to_owned());
+    }
+    Finding::graded(grade, details.join(", "))
+}
+
+pub async fn audit_mx<L>(lookup: &L, domain: &str) -> Finding
+where
+    L: Lookup + ?Sized,
+{
+    let name = match Name::new(domain) {
+        Ok(name) => name,
+        Err(e) => return Finding::failed(e.to_string()),
+    };
+    match lookup.lookup_mx(&name).await {
+        Ok(exchanges) if !exchanges.is_empty() => {
+            let exchanges: Vec<&str> = exchanges
+                .iter()
+                .map(|e| e.as_str().trim_end_matches('.'))
+                .collect();
+            Finding::graded(Grade::A, exchanges.join(" "))
+        }
+        Ok(_) | Err(LookupError::NoRecords) => Finding::graded(Grade::F, "no MX record".to_owned()),
+        Err(e) => Finding::failed(e.to_string()),
+    }
+}
+
+/// Checks the DNS record announcing an MTA-STS policy, the policy file itself is not fetched
+pub async fn audit_mta_sts<L>(lookup: &L, domain: &str) -> Finding
+where
+    L: Lookup + ?Sized,
+{
+    match get_records(lookup, &format!("_mta-sts.{}", domain), "v=STSv1").await {
+        Ok(records) => match records.as_slice() {
+            [] => Finding::graded(Grade::F, "no MTA-STS record".to_owned()),
+            [record] => match get_tag(record, "id") {
+                Some(id) => Finding::graded(Grade::A, format!("id={}", id)),
+                None => Finding::graded(Grade::C, format!("no id: {}", record)),
+            },
+            _ => Finding::graded(Grade::C, "multiple records".to_owned()),
+        },
+        Err(e) => Finding::failed(e),
+    }
+}
+
+pub async fn audit_tls_rpt<L>(lookup: &L, domain: &str) -> Finding
+where
+    L: Lookup + ?Sized,
+{
+    match get_records(lookup, &format!("_smtp._tls.{}", domain), "v=TLSRPTv1").await {
+        Ok(records) => match records.as_slice() {
+            [] => Finding::graded(Grade::F, "no TLS-RPT record".to_owned()),
+            [record] => match get_tag(record, "rua") {
+                Some(rua) => Finding::graded(Grade::A, format!("rua={}", rua)),
+                None => Finding::graded(Grade::C, format!("no rua: {}", record)),
+            },
+            _ => Finding::graded(Grade::C, "multiple records".to_owned()),
+        },
+        Err(e) => Finding::failed(e),
+    }
+}
+
+/// Looks for public keys at the common selectors, a missing key does not prove that DKIM is
+/// not used, so the grade is D instead of F
+pub async fn audit_dkim<L>(lookup: &L, domain: &str) -> Finding
+where
+    L: Lookup + ?Sized,
+{
+    let mut selectors = Vec::new();
+    let mut error = None;
+    for selector in DKIM_SELECTORS {
+        let name = format!("{}._domainkey.{}", selector, domain);
+        match get_records(lookup, &name, "").await {
+            // An empty key means that the key was revoked
+            Ok(records)
+                if records
+                    .iter()
+                    .any(|r| get_tag(r, "p").is_some_and(|p| !p.is_empty())) =>
+            {
+                selectors.push(*selector)
+            }
+            Ok(_) => {}
+            Err(e) => error = Some(e),
+        }
+    }
+    match (selectors.is_empty(), error) {
+        (false, _) => Finding::graded(Grade::A, format!("selectors {}", selectors.join(" "))),
+        (true, Some(e)) => Finding::failed(e),
+        (true, None) => Finding::graded(Grade::D, "no key for the common selectors".to_owned()),
+    }
+}
+
+/// Email security of the domain of one company, flat so that it can also be written as CSV
+#[derive(Debug, Serialize, Clone)]
+pub struct EmailAudit {
+    pub company: String,
+    pub domain: String,
+    /// The worst grade of SPF, DMARC and MX, only an A with MTA-STS and TLS-RPT. DKIM is left
+    /// out, as a key that is not at one of the common selectors may still exist.
+    pub grade: Option<Grade>,
+    pub spf: Option<Grade>,
+    pub spf_details: String,
+    pub dmarc: Option<Grade>,
+    pub dmarc_details: String,
+    pub mx: Option<Grade>,
+    pub mx_details: String,
+    pub mta_sts: Option<Grade>,
+    pub mta_sts_details: String,
+    pub tls_rpt: Option<Grade>,
+    pub tls_rpt_details: String,
+    pub dkim: Option<Grade>,
+    pub dkim_details: String,
+    pub timestamp: String,
+}
+
+impl EmailAudit {
+    fn get_findings(&self) -> [(&'static str, Option<Grade>, &str); 6] {
+        [
+            ("SPF", self.spf, &self.spf_details),
+            ("DMARC", self.dmarc, &self.dmarc_details),
+            ("MX", self.mx, &self.mx_details),
+            ("MTA-STS", self.mta_sts, &self.mta_sts_details),
+            ("TLS-RPT", self.tls_rpt, &self.tls_rpt_details),
+            ("DKIM", self.dkim, &self.dkim_details),
+        ]
+    }
+}
+
+impl fmt::Display for EmailAudit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let findings: Vec<String> = self
+            .get_findings()
+            .iter()
+            .map(|(name, grade, _)| format!("{} {}", name, format_grade(*grade)))
+            .collect();
+        write!(
+            f,
+            "{}, {}: {} ({})",
+            self.company,
+            self.domain,
+            format_grade(self.grade),
+            findings.join(", ")
+        )
+    }
+}
+
+fn get_overall_grade(core: &[&Finding], transport: &[&Finding]) -> Option<Grade> {
+    let mut grade = Grade::A;
+    for finding in core {
+        grade = grade.max(finding.grade?);
+    }
+    if grade == Grade::A && transport.iter().any(|f| f.grade != Some(Grade::A)) {
+        grade = Grade::B;
+    }
+    Some(grade)
+}
+
+pub async fn audit_domain<L>(lookup: &L, company: &str, domain: &str) -> EmailAudit
+where
+    L: Lookup + ?Sized,
+{
+    let spf = audit_spf(lookup, domain).await;
+    let dmarc = audit_dmarc(lookup, domain).await;
+    let mx = audit_mx(lookup, domain).await;
+    let mta_sts = audit_mta_sts(lookup, domain).await;
+    let tls_rpt = audit_tls_rpt(lookup, domain).await;
+    let dkim = audit_dkim(lookup, domain).await;
+    EmailAudit {
+        company: company.to_owned(),
+        domain: domain.to_owned(),
+        grade: get_overall_grade(&[&spf, &dmarc, &mx], &[&mta_sts, &tls_rpt]),
+        spf: spf.grade,
+        spf_details: spf.details,
+        dmarc: dmarc.grade,
+        dmarc_details: dmarc.details,
+        mx: mx.grade,
+        mx_details: mx.details,
+        mta_sts: mta_sts.grade,
+        mta_sts_details: mta_sts.details,
+        tls_rpt: tls_rpt.grade,
+        tls_rpt_details: tls_rpt.details,
+        dkim: dkim.grade,
+        dkim_details: dkim.details,
+        timestamp: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+    }
+}
+
+pub async fn audit_email<L>(lookup: &L, metadata: &CompanyMetadata) -> EmailAudit
+where
+    L: Lookup + ?Sized,
+{
+    audit_domain(lookup, &metadata.name, &metadata.get_domainname(true)).await
+}
+
+/// Writes the grades as aligned table, followed by the details of everything below A
+pub fn write_audit_table<W: Write>(mut out: W, audits: &[EmailAudit]) -> io::Result<()> {
+    let mut rows = vec![vec![
+        "Company", "Domain", "Grade", "SPF", "DMARC", "MX", "MTA-STS", "TLS-RPT", "DKIM",
+    ]
+    .into_iter()
+    .map(String::from)
+    .collect::<Vec<String>>()];
+    for audit in audits {
+        let mut row = vec![
+            audit.company.clone(),
+            audit.domain.clone(),
+            format_grade(audit.grade),
+        ];
+        row.extend(
+            audit
+                .get_findings()
+                .iter()
+                .map(|(_, grade, _)| format_grade(*grade)),
+        );
+        rows.push(row);
+    }
+    let mut widths = vec![0; rows[0].len()];
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    for row in &rows {
+        let cells: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        writeln!(out, "{}", cells.join("  ").trim_end())?;
+    }
+    for audit in audits {
+        let findings: Vec<_> = audit
+            .get_findings()
+            .iter()
+            .filter(|(_, grade, _)| *grade != Some(Grade::A))
+            .map(|(name, grade, details)| {
+                format!("  {} {}: {}", name, format_grade(*grade), details)
+            })
+            .collect();
+        if !findings.is_empty() {
+            writeln!(out)?;
+            writeln!(out, "{} ({})", audit.company, audit.domain)?;
+            for finding in findings {
+                writeln!(out, "{}", finding)?;
+            }
+        }
+    }
+    Ok(())
+}
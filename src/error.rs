@@ -0,0 +1,77 @@
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Errors of the library, file errors contain the path of the file
+#[derive(Debug)]
+pub enum Error {
+    Io {
+        path: PathBuf,
+        source: io::Error,
+    },
+    Json {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+    Csv {
+        path: PathBuf,
+        source: csv::Error,
+    },
+    Regex(regex::Error),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    /// For use with map_err, e.g. `fs::read(path).map_err(Error::io(path))`
+    pub fn io(path: &Path) -> impl FnOnce(io::Error) -> Error + '_ {
+        move |source| Error::Io {
+            path: path.to_owned(),
+            source,
+        }
+    }
+
+    pub fn json(path: &Path) -> impl FnOnce(serde_json::Error) -> Error + '_ {
+        move |source| Error::Json {
+            path: path.to_owned(),
+            source,
+        }
+    }
+
+    pub fn csv(path: &Path) -> impl FnOnce(csv::Error) -> Error + '_ {
+        move |source| Error::Csv {
+            path: path.to_owned(),
+            source,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            Error::Json { path, source } => {
+                write!(f, "{}: invalid JSON: {}", path.display(), source)
+            }
+            Error::Csv { path, source } => write!(f, "{}: {}", path.display(), source),
+            Error::Regex(e) => write!(f, "Invalid regular expression: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            Error::Json { source, .. } => Some(source),
+            Error::Csv { source, .. } => Some(source),
+            Error::Regex(e) => Some(e),
+        }
+    }
+}
+
+impl From<regex::Error> for Error {
+    fn from(e: regex::Error) -> Error {
+        Error::Regex(e)
+    }
+}
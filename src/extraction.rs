@@ -1,34 +1,626 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
 use std::fs::File;
 use std::io::prelude::*;
-use std::path::Path;
-
-use crate::data_structures::CompanyDownloads;
-
-pub fn extract_text(root_dir: &Path, companies: &[CompanyDownloads]) {
-    for c in companies {
-        for d in &c.downloads {
-            let mut file_path = d.report.get_file_path(root_dir);
-            eprintln!("{:?}", file_path);
-
-            let doc = lopdf::Document::load(&file_path);
-
-            //let contents_result = pdf_extract::extract_text(&file_path);
-            file_path.set_extension("txt");
-            match doc {
-                Ok(doc) => {
-                    let total = *doc.get_pages().keys().max().unwrap_or(&0);
-                    //let content = doc.extract_text(&[1]);//1..total);
-                    let content = doc.extract_text(&(1..total).collect::<Vec<u32>>()[..]);
-                    match content {
-                        Ok(content) => {
-                            let mut file = File::create(&file_path).unwrap();
-                            file.write_fmt(format_args!("{}", content)).unwrap();
-                        }
-                        Err(e) => println!("Error: {}", e),
-                    }
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::rc::Rc;
+use std::str::FromStr;
+use std::sync::{mpsc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use log::{error, info, warn};
+use pdf_extract::{ConvertToFmt, MediaBox, OutputDev, OutputError, PlainTextOutput, Transform};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::data_structures::{Configuration, Download, Language, Report};
+use crate::error::{self, Error};
+use crate::ocr;
+use crate::store::{link_or_copy, DocumentStore};
+
+pub type ExtractionError = Box<dyn std::error::Error + Send + Sync>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    Lopdf,
+    PdfExtract,
+    Pdftotext,
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Backend::Lopdf => write!(f, "lopdf"),
+            Backend::PdfExtract => write!(f, "pdf-extract"),
+            Backend::Pdftotext => write!(f, "pdftotext"),
+        }
+    }
+}
+
+impl FromStr for Backend {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "lopdf" => Ok(Backend::Lopdf),
+            "pdf-extract" => Ok(Backend::PdfExtract),
+            "pdftotext" => Ok(Backend::Pdftotext),
+            _ => Err(format!(
+                "Unknown extractor '{}', use lopdf, pdf-extract or pdftotext",
+                name
+            )),
+        }
+    }
+}
+
+impl Backend {
+    pub fn is_available(&self) -> bool {
+        match self {
+            Backend::Lopdf | Backend::PdfExtract => true,
+            Backend::Pdftotext => Command::new("pdftotext").arg("-v").output().is_ok(),
+        }
+    }
+
+    // Returns the text of every page of the document. Only pdftotext is stopped after the
+    // timeout, the pdf libraries run in this process and can't be interrupted.
+    pub fn extract_pages(
+        &self,
+        file_path: &Path,
+        timeout: Duration,
+    ) -> Result<Vec<String>, ExtractionError> {
+        // Both pdf libraries panic on some malformed documents
+        let result = catch_unwind(AssertUnwindSafe(|| match self {
+            Backend::Lopdf => extract_lopdf(file_path),
+            Backend::PdfExtract => extract_pdf_extract(file_path),
+            Backend::Pdftotext => extract_pdftotext(file_path, timeout),
+        }));
+        match result {
+            Ok(pages) => pages,
+            Err(_) => Err(format!("{} panicked", self).into()),
+        }
+    }
+}
+
+fn extract_lopdf(file_path: &Path) -> Result<Vec<String>, ExtractionError> {
+    let doc = lopdf::Document::load(file_path)?;
+    let mut pages = Vec::new();
+    for page_number in doc.get_pages().keys() {
+        pages.push(doc.extract_text(&[*page_number])?);
+    }
+    Ok(pages)
+}
+
+struct PageText(Rc<RefCell<String>>);
+
+struct PageTextWriter(Rc<RefCell<String>>);
+
+impl fmt::Write for PageTextWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0.borrow_mut().push_str(s);
+        Ok(())
+    }
+}
+
+impl ConvertToFmt for PageText {
+    type Writer = PageTextWriter;
+
+    fn convert(self) -> Self::Writer {
+        PageTextWriter(self.0)
+    }
+}
+
+// Uses the plain text output of pdf-extract but collects the text of every page separately
+struct PagedOutput {
+    output: PlainTextOutput<PageText>,
+    current_page: Rc<RefCell<String>>,
+    pages: Vec<String>,
+}
+
+impl OutputDev for PagedOutput {
+    fn begin_page(
+        &mut self,
+        page_num: u32,
+        media_box: &MediaBox,
+        art_box: Option<(f64, f64, f64, f64)>,
+    ) -> Result<(), OutputError> {
+        self.output.begin_page(page_num, media_box, art_box)
+    }
+
+    fn end_page(&mut self) -> Result<(), OutputError> {
+        self.output.end_page()?;
+        self.pages.push(self.current_page.replace(String::new()));
+        Ok(())
+    }
+
+    fn output_character(
+        &mut self,
+        trm: &Transform,
+        width: f64,
+        spacing: f64,
+        font_size: f64,
+        char: &str,
+    ) -> Result<(), OutputError> {
+        self.output
+            .output_character(trm, width, spacing, font_size, char)
+    }
+
+    fn begin_word(&mut self) -> Result<(), OutputError> {
+        self.output.begin_word()
+    }
+
+    fn end_word(&mut self) -> Result<(), OutputError> {
+        self.output.end_word()
+    }
+
+    fn end_line(&mut self) -> Result<(), OutputError> {
+        self.output.end_line()
+    }
+}
+
+fn extract_pdf_extract(file_path: &Path) -> Result<Vec<String>, ExtractionError> {
+    let doc = pdf_extract_lopdf::Document::load(file_path)?;
+    let current_page = Rc::new(RefCell::new(String::new()));
+    let mut output = PagedOutput {
+        output: PlainTextOutput::new(PageText(current_page.clone())),
+        current_page,
+        pages: Vec::new(),
+    };
+    pdf_extract::output_doc(&doc, &mut output).map_err(|e| format!("{:?}", e))?;
+    Ok(output.pages)
+}
+
+fn extract_pdftotext_in(
+    directory: &Path,
+    file_path: &Path,
+    timeout: Duration,
+) -> Result<Vec<String>, ExtractionError> {
+    let text_path = directory.join("text.txt");
+    ocr::run(
+        Command::new("pdftotext")
+            .args(["-layout", "-enc", "UTF-8"])
+            .arg(file_path)
+            .arg(&text_path),
+        directory,
+        timeout,
+    )?;
+    // pdftotext ends every page with a form feed
+    let text = String::from_utf8_lossy(&fs::read(&text_path)?).into_owned();
+    let mut pages: Vec<String> = text.split('\x0c').map(|p| p.to_owned()).collect();
+    if pages.last().is_some_and(|p| p.trim().is_empty()) {
+        pages.pop();
+    }
+    Ok(pages)
+}
+
+// pdftotext is killed after the timeout, so that it doesn't keep running after the document was
+// given up
+fn extract_pdftotext(file_path: &Path, timeout: Duration) -> Result<Vec<String>, ExtractionError> {
+    let directory = ocr::create_work_directory()?;
+    let result = extract_pdftotext_in(&directory, file_path, timeout);
+    let _ = fs::remove_dir_all(&directory);
+    result
+}
+
+fn is_empty(pages: &[String]) -> bool {
+    pages.iter().all(|p| p.trim().is_empty())
+}
+
+// Tries the backends in order until one of them returns some text. If all of them come back empty
+// the first empty result is returned, an error only if every backend failed. The backends share
+// the timeout.
+pub fn extract_with_fallback(
+    file_path: &Path,
+    backends: &[Backend],
+    timeout: Duration,
+) -> Result<(Backend, Vec<String>), ExtractionError> {
+    let start = Instant::now();
+    let mut empty_result = None;
+    let mut last_error: Option<ExtractionError> = None;
+    for backend in backends {
+        if start.elapsed() >= timeout {
+            break;
+        }
+        if !backend.is_available() {
+            continue;
+        }
+        match backend.extract_pages(file_path, timeout.saturating_sub(start.elapsed())) {
+            Ok(pages) if is_empty(&pages) => {
+                warn!("{} found no text in {:?}", backend, file_path);
+                if empty_result.is_none() {
+                    empty_result = Some((*backend, pages));
+                }
+            }
+            Ok(pages) => return Ok((*backend, pages)),
+            Err(e) => {
+                warn!("{} failed for {:?}: {}", backend, file_path, e);
+                last_error = Some(e);
+            }
+        }
+    }
+    match (empty_result, last_error) {
+        (Some(result), _) => Ok(result),
+        (None, Some(e)) => Err(e),
+        (None, None) => Err("No text extractor available".into()),
+    }
+}
+
+const PAGE_MARKER: &str = "=== Page ";
+
+pub fn get_text_path(file_path: &Path) -> PathBuf {
+    file_path.with_extension("txt")
+}
+
+pub fn write_pages(text_path: &Path, pages: &[String]) -> error::Result<()> {
+    let mut file = File::create(text_path).map_err(Error::io(text_path))?;
+    for (index, page) in pages.iter().enumerate() {
+        writeln!(file, "{}{} ===", PAGE_MARKER, index + 1)
+            .and_then(|_| writeln!(file, "{}", page.trim_end()))
+            .map_err(Error::io(text_path))?;
+    }
+    Ok(())
+}
+
+pub fn read_pages(text_path: &Path) -> error::Result<Vec<String>> {
+    let content = fs::read_to_string(text_path).map_err(Error::io(text_path))?;
+    let mut pages: Vec<String> = Vec::new();
+    for line in content.lines() {
+        if line.starts_with(PAGE_MARKER) && line.ends_with(" ===") {
+            pages.push(String::new());
+        } else if let Some(page) = pages.last_mut() {
+            page.push_str(line);
+            page.push('\n');
+        }
+    }
+    Ok(pages)
+}
+
+const MIN_KEYWORD_LENGTH: usize = 6;
+
+/// The most frequent longer words of a document, used as keywords for the search page
+pub fn get_keywords(pages: &[String], count: usize) -> Vec<String> {
+    let mut frequencies: HashMap<String, usize> = HashMap::new();
+    for page in pages {
+        let words = page
+            .split(|c: char| !c.is_alphabetic())
+            .filter(|w| w.chars().count() >= MIN_KEYWORD_LENGTH);
+        for word in words {
+            *frequencies.entry(word.to_lowercase()).or_default() += 1;
+        }
+    }
+    let mut frequencies: Vec<(String, usize)> = frequencies.into_iter().collect();
+    frequencies.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    frequencies
+        .into_iter()
+        .take(count)
+        .map(|(word, _)| word)
+        .collect()
+}
+
+/// Keywords of all downloads whose text is in the extraction cache, keyed by document hash
+pub fn load_keywords(
+    c: &Configuration,
+    downloads: &[Download],
+    count: usize,
+) -> HashMap<String, Vec<String>> {
+    let store = DocumentStore::new(&c.get_store_directory());
+    let mut keywords = HashMap::new();
+    for download in downloads {
+        if keywords.contains_key(&download.sha256) {
+            continue;
+        }
+        if let Ok(pages) = read_pages(&store.get_related_path(&download.sha256, "txt")) {
+            keywords.insert(download.sha256.clone(), get_keywords(&pages, count));
+        }
+    }
+    keywords
+}
+
+// Increase whenever a change to the extraction changes its output, to invalidate the cache
+const EXTRACTOR_VERSION: u32 = 1;
+
+pub fn get_extractor_version(backends: &[Backend], ocr: bool) -> String {
+    let backends: Vec<String> = backends.iter().map(|b| b.to_string()).collect();
+    let ocr = if ocr { "+ocr" } else { "" };
+    format!("{}:{}{}", EXTRACTOR_VERSION, backends.join(","), ocr)
+}
+
+// Replaces the text of pages without any text by the OCR result, returns the OCR'd pages
+fn ocr_empty_pages(
+    file_path: &Path,
+    pages: &mut [String],
+    language: &Language,
+    timeout: Duration,
+) -> Vec<usize> {
+    let mut ocr_pages = Vec::new();
+    for (index, page) in pages.iter_mut().enumerate() {
+        if !page.trim().is_empty() {
+            continue;
+        }
+        match ocr::ocr_page(file_path, index + 1, language, timeout) {
+            Ok(text) => {
+                *page = text;
+                ocr_pages.push(index + 1);
+            }
+            Err(e) => warn!("OCR of page {} of {:?} failed: {}", index + 1, file_path, e),
+        }
+    }
+    ocr_pages
+}
+
+// Stored next to the extracted text of a document in the document store
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct CachedExtraction {
+    extractor_version: String,
+    backend: String,
+    pages: usize,
+    #[serde(default)]
+    ocr_pages: Vec<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ExtractionRecord {
+    pub report: Report,
+    pub sha256: String,
+    pub extractor_version: String,
+    pub backend: Option<String>,
+    pub pages: usize,
+    /// Pages without a text layer whose text was recognized with OCR, starting at 1
+    #[serde(default)]
+    pub ocr_pages: Vec<usize>,
+    pub duration_ms: u64,
+    /// The text was taken from the store, only set if there was no error
+    pub cached: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct ExtractionManifest {
+    pub extractor_version: String,
+    pub documents: Vec<ExtractionRecord>,
+}
+
+impl ExtractionManifest {
+    pub fn get_path(run_directory: &Path) -> PathBuf {
+        run_directory.join("extraction.json")
+    }
+
+    pub fn load(run_directory: &Path) -> error::Result<ExtractionManifest> {
+        let path = ExtractionManifest::get_path(run_directory);
+        let contents = fs::read_to_string(&path).map_err(Error::io(&path))?;
+        serde_json::from_str(&contents).map_err(Error::json(&path))
+    }
+
+    pub fn write(&self, run_directory: &Path) -> error::Result<()> {
+        let path = ExtractionManifest::get_path(run_directory);
+        let serialized = serde_json::to_string_pretty(self).map_err(Error::json(&path))?;
+        fs::write(&path, serialized).map_err(Error::io(&path))
+    }
+
+    pub fn get_failures(&self) -> impl Iterator<Item = &ExtractionRecord> {
+        self.documents.iter().filter(|d| d.error.is_some())
+    }
+}
+
+pub struct ExtractionJob {
+    pub report: Report,
+    pub sha256: String,
+    pub file_path: PathBuf,
+}
+
+impl ExtractionJob {
+    pub fn from_download(run_directory: &Path, download: &Download) -> ExtractionJob {
+        ExtractionJob {
+            report: download.report.clone(),
+            sha256: download.sha256.clone(),
+            file_path: download.report.get_file_path(run_directory),
+        }
+    }
+}
+
+pub struct Extractor {
+    store: DocumentStore,
+    backends: Vec<Backend>,
+    extractor_version: String,
+    ocr: bool,
+    timeout: Duration,
+}
+
+impl Extractor {
+    pub fn new(c: &Configuration, timeout: Duration) -> Extractor {
+        let ocr = !c.no_ocr && ocr::is_available();
+        if !c.no_ocr && !ocr {
+            warn!("tesseract or pdftoppm not found, pages without text are not OCR'd");
+        }
+        Extractor {
+            store: DocumentStore::new(&c.get_store_directory()),
+            backends: c.extractors.clone(),
+            extractor_version: get_extractor_version(&c.extractors, ocr),
+            ocr,
+            timeout,
+        }
+    }
+
+    fn load_cached(&self, sha256: &str) -> Option<CachedExtraction> {
+        let contents = fs::read_to_string(self.store.get_related_path(sha256, "json")).ok()?;
+        let cached: CachedExtraction = serde_json::from_str(&contents).ok()?;
+        let is_current = cached.extractor_version == self.extractor_version
+            && self.store.get_related_path(sha256, "txt").exists();
+        if is_current {
+            Some(cached)
+        } else {
+            None
+        }
+    }
+
+    // Runs the extraction on its own thread so that a document that takes forever only costs
+    // the timeout. pdftotext is killed after the timeout, but lopdf and pdf-extract run in this
+    // process and keep running on the thread that is left behind until they are done. It only
+    // sends its result back and never writes to the store, so a late result is simply dropped.
+    fn extract_with_timeout(
+        &self,
+        job: &ExtractionJob,
+    ) -> Result<(Backend, Vec<String>), ExtractionError> {
+        let (sender, receiver) = mpsc::channel();
+        let file_path = job.file_path.clone();
+        let backends = self.backends.clone();
+        let timeout = self.timeout;
+        thread::spawn(move || {
+            let _ = sender.send(extract_with_fallback(&file_path, &backends, timeout));
+        });
+        match receiver.recv_timeout(self.timeout) {
+            Ok(result) => result,
+            Err(_) => Err(format!("Timed out after {} s", self.timeout.as_secs()).into()),
+        }
+    }
+
+    // OCR is not part of the timeout of the document, as a long scanned report needs much longer
+    // than one with a text layer. Instead every page has the timeout.
+    fn extract_text(
+        &self,
+        job: &ExtractionJob,
+    ) -> Result<(Backend, Vec<String>, Vec<usize>), ExtractionError> {
+        let (backend, mut pages) = self.extract_with_timeout(job)?;
+        let ocr_pages = if self.ocr {
+            ocr_empty_pages(
+                &job.file_path,
+                &mut pages,
+                &job.report.language,
+                self.timeout,
+            )
+        } else {
+            Vec::new()
+        };
+        Ok((backend, pages, ocr_pages))
+    }
+
+    // Puts the extracted text next to the document in the run directory
+    fn link_text(&self, job: &ExtractionJob) -> Result<(), ExtractionError> {
+        let run_text_path = get_text_path(&job.file_path);
+        if run_text_path.exists() {
+            fs::remove_file(&run_text_path)?;
+        }
+        link_or_copy(
+            &self.store.get_related_path(&job.sha256, "txt"),
+            &run_text_path,
+        )?;
+        Ok(())
+    }
+
+    fn extract(&self, job: &ExtractionJob) -> ExtractionRecord {
+        let start = Instant::now();
+        let mut record = ExtractionRecord {
+            report: job.report.clone(),
+            sha256: job.sha256.clone(),
+            extractor_version: self.extractor_version.clone(),
+            backend: None,
+            pages: 0,
+            ocr_pages: Vec::new(),
+            duration_ms: 0,
+            cached: false,
+            error: None,
+        };
+        let text_path = self.store.get_related_path(&job.sha256, "txt");
+        let mut from_cache = false;
+        let result = match self.load_cached(&job.sha256) {
+            Some(cached) => {
+                from_cache = true;
+                Ok(cached)
+            }
+            None => self
+                .extract_text(job)
+                .and_then(|(backend, pages, ocr_pages)| {
+                    write_pages(&text_path, &pages)?;
+                    let cached = CachedExtraction {
+                        extractor_version: self.extractor_version.clone(),
+                        backend: backend.to_string(),
+                        pages: pages.len(),
+                        ocr_pages,
+                    };
+                    fs::write(
+                        self.store.get_related_path(&job.sha256, "json"),
+                        serde_json::to_string_pretty(&cached)?,
+                    )?;
+                    Ok(cached)
+                }),
+        };
+        let result = result.and_then(|cached| {
+            self.link_text(job)?;
+            Ok(cached)
+        });
+        match result {
+            Ok(cached) => {
+                record.cached = from_cache;
+                record.backend = Some(cached.backend);
+                record.pages = cached.pages;
+                record.ocr_pages = cached.ocr_pages;
+            }
+            Err(e) => {
+                error!("Extracting text from {:?} failed: {}", job.file_path, e);
+                record.error = Some(e.to_string());
+            }
+        }
+        record.duration_ms = start.elapsed().as_millis() as u64;
+        record
+    }
+
+    /// Extracts the text of all documents with a pool of worker threads. Documents whose text
+    /// was already extracted with the same extractors are taken from the cache. Documents with
+    /// the same content are only extracted once, so that no two workers write the same files
+    /// in the store.
+    pub fn extract_all(&self, jobs: Vec<ExtractionJob>, workers: usize) -> ExtractionManifest {
+        let total = jobs.len();
+        let mut first_jobs: HashMap<String, usize> = HashMap::new();
+        let mut unique = Vec::new();
+        let mut duplicates = Vec::new();
+        for (index, job) in jobs.into_iter().enumerate() {
+            match first_jobs.get(&job.sha256) {
+                Some(first) => duplicates.push((index, *first, job)),
+                None => {
+                    first_jobs.insert(job.sha256.clone(), index);
+                    unique.push((index, job));
                 }
-                Err(error) => println!("Error: {}", error),
             }
         }
+        let jobs = Mutex::new(unique.into_iter());
+        let records = Mutex::new(HashMap::with_capacity(total));
+        thread::scope(|scope| {
+            for _ in 0..workers.max(1) {
+                scope.spawn(|| loop {
+                    let next_job = jobs.lock().unwrap().next();
+                    let (index, job) = match next_job {
+                        Some(next_job) => next_job,
+                        None => break,
+                    };
+                    info!("Extracting text from {:?}", job.file_path);
+                    let record = self.extract(&job);
+                    records.lock().unwrap().insert(index, record);
+                });
+            }
+        });
+        let mut records = records.into_inner().unwrap();
+        for (index, first, job) in duplicates {
+            let mut record = records[&first].clone();
+            record.report = job.report.clone();
+            record.duration_ms = 0;
+            if record.error.is_none() {
+                record.cached = true;
+                if let Err(e) = self.link_text(&job) {
+                    error!("Linking the text of {:?} failed: {}", job.file_path, e);
+                    record.cached = false;
+                    record.error = Some(e.to_string());
+                }
+            }
+            records.insert(index, record);
+        }
+        let mut records: Vec<(usize, ExtractionRecord)> = records.into_iter().collect();
+        records.sort_by_key(|(index, _)| *index);
+        ExtractionManifest {
+            extractor_version: self.extractor_version.clone(),
+            documents: records.into_iter().map(|(_, record)| record).collect(),
+        }
     }
 }
@@ -0,0 +1,589 @@
+use regex::{Regex, RegexBuilder};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use crate::data_structures::{Language, Report, ReportType};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum Figure {
+    Revenue,
+    NetProfit,
+    TotalAssets,
+    Equity,
+    Employees,
+    DividendPerShare,
+}
+
+impl Figure {
+    pub const ALL: &'static [Figure] = &[
+        Figure::Revenue,
+        Figure::NetProfit,
+        Figure::TotalAssets,
+        Figure::Equity,
+        Figure::Employees,
+        Figure::DividendPerShare,
+    ];
+
+    fn labels(&self) -> &'static [(Language, &'static str)] {
+        match self {
+            Figure::Revenue => &[
+                (Language::EN, "net revenues?"),
+                (Language::EN, "total revenues?"),
+                (Language::EN, "revenues?"),
+                (Language::EN, "net sales"),
+                (Language::EN, "total operating income"),
+                (Language::DE, "nettoumsatz"),
+                (Language::DE, "umsatzerlöse"),
+                (Language::DE, "umsatz"),
+                (Language::DE, "nettoerlöse"),
+                (Language::DE, "geschäftsertrag"),
+                (Language::DE, "betriebsertrag"),
+                (Language::FR, "chiffre d['’]affaires"),
+                (Language::FR, "produits? d['’]exploitation"),
+                (Language::FR, "total des produits"),
+                (Language::IT, "ricavi"),
+                (Language::IT, "fatturato"),
+            ],
+            Figure::NetProfit => &[
+                (Language::EN, "net profit"),
+                (Language::EN, "net income"),
+                (Language::EN, "profit for the (?:year|period)"),
+                (Language::EN, "group profit"),
+                (Language::DE, "konzerngewinn"),
+                (Language::DE, "reingewinn"),
+                (Language::DE, "jahresgewinn"),
+                (Language::DE, "konzernergebnis"),
+                (Language::DE, "reinergebnis"),
+                (Language::FR, "bénéfice net"),
+                (Language::FR, "résultat net"),
+                (Language::FR, "bénéfice consolidé"),
+                (Language::FR, "bénéfice de l['’]exercice"),
+                (Language::IT, "utile netto"),
+                (Language::IT, "utile d['’]esercizio"),
+            ],
+            Figure::TotalAssets => &[
+                (Language::EN, "total assets"),
+                (Language::EN, "balance sheet total"),
+                (Language::DE, "bilanzsumme"),
+                (Language::DE, "total aktiven"),
+                (Language::DE, "summe aktiven"),
+                (Language::FR, "total du bilan"),
+                (Language::FR, "total des actifs"),
+                (Language::FR, "total de l['’]actif"),
+                (Language::IT, "totale attivo"),
+                (Language::IT, "totale di bilancio"),
+            ],
+            Figure::Equity => &[
+                (Language::EN, "total equity"),
+                (Language::EN, "shareholders['’]? equity"),
+                (Language::EN, "equity"),
+                (Language::DE, "total eigenkapital"),
+                (Language::DE, "eigenkapital"),
+                (Language::FR, "total des fonds propres"),
+                (Language::FR, "fonds propres"),
+                (Language::FR, "capitaux propres"),
+                (Language::IT, "patrimonio netto"),
+            ],
+            Figure::Employees => &[
+                (Language::EN, "full[- ]time equivalents"),
+                (Language::EN, "employees"),
+                (Language::EN, "headcount"),
+                (Language::DE, "vollzeitstellen"),
+                (Language::DE, "mitarbeitende"),
+                (Language::DE, "mitarbeiter(?:innen)?"),
+                (Language::DE, "personalbestand"),
+                (Language::FR, "équivalents? plein temps"),
+                (Language::FR, "collaborateurs"),
+                (Language::FR, "employés"),
+                (Language::IT, "collaboratori"),
+                (Language::IT, "dipendenti"),
+            ],
+            Figure::DividendPerShare => &[
+                (Language::EN, "dividend per (?:registered )?share"),
+                (Language::DE, "dividende (?:pro|je) (?:namen)?aktie"),
+                (Language::FR, "dividende par action"),
+                (Language::IT, "dividendo per azione"),
+            ],
+        }
+    }
+
+    // Lines with these words contain related figures, e.g. the equity ratio
+    fn exclusions(&self) -> &'static [&'static str] {
+        match self {
+            Figure::Revenue => &["growth", "wachstum", "croissance", "crescita"],
+            Figure::NetProfit => &[
+                "per share",
+                "pro aktie",
+                "je aktie",
+                "par action",
+                "per azione",
+                "margin",
+                "marge",
+                "margine",
+            ],
+            Figure::Equity => &[
+                "ratio",
+                "return on",
+                "quote",
+                "rendite",
+                "rendement",
+                "rendimento",
+                "liabilities",
+                "passifs",
+            ],
+            Figure::TotalAssets => &["return on", "rendite", "rendement"],
+            Figure::Employees => &[
+                "per employee",
+                "pro mitarbeite",
+                "par collaborat",
+                "benefit",
+            ],
+            Figure::DividendPerShare => &["yield", "rendite", "rendement", "payout"],
+        }
+    }
+
+    fn is_scaled(&self) -> bool {
+        !matches!(self, Figure::Employees | Figure::DividendPerShare)
+    }
+}
+
+impl fmt::Display for Figure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Figure::Revenue => "revenue",
+            Figure::NetProfit => "net_profit",
+            Figure::TotalAssets => "total_assets",
+            Figure::Equity => "equity",
+            Figure::Employees => "employees",
+            Figure::DividendPerShare => "dividend_per_share",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct KeyFigure {
+    pub company: String,
+    pub year: u16,
+    pub figure: Figure,
+    pub value: f64,
+    pub unit: String,
+    pub confidence: f32,
+    pub report_type: ReportType,
+    pub language: Language,
+    pub page: usize,
+    pub label: String,
+    pub text: String,
+}
+
+struct Scale {
+    factor: f64,
+    currency: Option<&'static str>,
+}
+
+pub struct KeyFigureExtractor {
+    labels: Vec<(Figure, Language, Regex)>,
+    number: Regex,
+    scales: Vec<(Regex, f64)>,
+    currencies: Vec<(Regex, &'static str)>,
+}
+
+fn build_regex(pattern: &str) -> Regex {
+    RegexBuilder::new(pattern)
+        .case_insensitive(true)
+        .build()
+        .unwrap()
+}
+
+// A number may use ' or ’ (Swiss), thin or non-breaking spaces (French) or commas as thousands
+// separators. Normal spaces separate the columns of a table and are not part of a number.
+const NUMBER_PATTERN: &str = r"\(?[-–−]?\d(?:[\d'’\u{00a0}\u{2009}\u{202f}]|[.,]\d)*(?:\)|%)?";
+
+// How many lines above a figure are searched for its unit
+const UNIT_CONTEXT_LINES: usize = 15;
+
+const MIN_YEAR: f64 = 1900.0;
+const MAX_YEAR: f64 = 2100.0;
+
+impl Default for KeyFigureExtractor {
+    fn default() -> Self {
+        KeyFigureExtractor::new()
+    }
+}
+
+impl KeyFigureExtractor {
+    pub fn new() -> KeyFigureExtractor {
+        let mut labels = Vec::new();
+        for figure in Figure::ALL {
+            for (language, label) in figure.labels() {
+                let pattern = format!(r"(?:^|[^\p{{L}}])({})(?:[^\p{{L}}]|$)", label);
+                labels.push((*figure, language.clone(), build_regex(&pattern)));
+            }
+        }
+        let scales = vec![
+            (
+                r"\b(?:billions?|bn|mrd|milliarden|milliards?|miliardi)\b",
+                1e9,
+            ),
+            (
+                r"(?:\bin millions\b|\bmillions? of\b|\b(?:chf|eur|usd) ?m(?:io|n)?\b|\bmio\.? ?(?:chf|eur|usd)\b|\bin mio\b|\bmillionen\b|\ben millions\b|\bmilioni\b)",
+                1e6,
+            ),
+            (
+                r"(?:\bin thousands\b|\bthousands of\b|\b(?:chf|eur|usd) ?(?:1[ '’]?000|000|k)\b|\bt ?(?:chf|eur|usd)\b|\bk ?(?:chf|eur|usd)\b|\bin tausend\b|\btausend\b|\ben milliers\b|\bmigliaia\b)",
+                1e3,
+            ),
+        ];
+        let currencies = vec![
+            (r"\bchf\b|\bfr\.", "CHF"),
+            (r"\beur\b|€", "EUR"),
+            (r"\busd\b|\bus\$|\$", "USD"),
+        ];
+        KeyFigureExtractor {
+            labels,
+            number: Regex::new(NUMBER_PATTERN).unwrap(),
+            scales: scales
+                .into_iter()
+                .map(|(p, factor)| (build_regex(p), factor))
+                .collect(),
+            currencies: currencies
+                .into_iter()
+                .map(|(p, currency)| (build_regex(p), currency))
+                .collect(),
+        }
+    }
+
+    fn find_scale(&self, text: &str) -> Option<Scale> {
+        let factor = self
+            .scales
+            .iter()
+            .find(|(regex, _)| regex.is_match(text))
+            .map(|(_, factor)| *factor)?;
+        let currency = self
+            .currencies
+            .iter()
+            .find(|(regex, _)| regex.is_match(text))
+            .map(|(_, currency)| *currency);
+        Some(Scale { factor, currency })
+    }
+
+    fn find_currency(&self, text: &str) -> Option<&'static str> {
+        self.currencies
+            .iter()
+            .find(|(regex, _)| regex.is_match(text))
+            .map(|(_, currency)| *currency)
+    }
+
+    // Looks for the unit on the line itself, then in the lines above (e.g. a table header) and
+    // finally at the top of the page. Returns the scale with a confidence bonus.
+    fn find_context_scale(&self, lines: &[&str], index: usize) -> (Option<Scale>, f32) {
+        if let Some(scale) = self.find_scale(lines[index]) {
+            return (Some(scale), 0.25);
+        }
+        let start = index.saturating_sub(UNIT_CONTEXT_LINES);
+        for line in lines[start..index].iter().rev() {
+            if let Some(scale) = self.find_scale(line) {
+                return (Some(scale), 0.15);
+            }
+        }
+        for line in lines.iter().take(5) {
+            if let Some(scale) = self.find_scale(line) {
+                return (Some(scale), 0.1);
+            }
+        }
+        (None, 0.0)
+    }
+
+    // The first plausible number after the label, for multi-year tables this is usually the
+    // column of the reporting year
+    fn find_number(&self, text: &str) -> Option<f64> {
+        for number in self.number.find_iter(text) {
+            let number = number.as_str();
+            // Percentages and footnotes like "1)" are never the figure itself
+            if number.ends_with('%') || (number.ends_with(')') && !number.starts_with('(')) {
+                continue;
+            }
+            let value = match parse_number(number) {
+                Some(value) => value,
+                None => continue,
+            };
+            let is_year = value.fract() == 0.0 && (MIN_YEAR..=MAX_YEAR).contains(&value);
+            if is_year && !number.contains(|c: char| !c.is_ascii_digit()) {
+                continue;
+            }
+            return Some(value);
+        }
+        None
+    }
+
+    /// Finds all candidates for the key figures in the pages of a report
+    pub fn extract(&self, report: &Report, pages: &[String]) -> Vec<KeyFigure> {
+        let mut candidates = Vec::new();
+        for (page_index, page) in pages.iter().enumerate() {
+            let lines: Vec<&str> = page.lines().collect();
+            for (index, line) in lines.iter().enumerate() {
+                let lowercase_line = line.to_lowercase();
+                for (figure, language, regex) in &self.labels {
+                    if figure
+                        .exclusions()
+                        .iter()
+                        .any(|e| lowercase_line.contains(e))
+                    {
+                        continue;
+                    }
+                    let label = match regex.captures(line).and_then(|c| c.get(1)) {
+                        Some(label) => label,
+                        None => continue,
+                    };
+                    let mut confidence = 0.4;
+                    // Text extraction often puts the label and the numbers on separate lines
+                    let value = match self.find_number(&line[label.end()..]) {
+                        Some(value) => value,
+                        None => match lines.get(index + 1).and_then(|l| self.find_number(l)) {
+                            Some(value) => {
+                                confidence -= 0.15;
+                                value
+                            }
+                            None => continue,
+                        },
+                    };
+                    if *language == report.language {
+                        confidence += 0.1;
+                    }
+                    if report.report_type == ReportType::AnnualReport
+                        || report.report_type == ReportType::FinancialReport
+                    {
+                        confidence += 0.05;
+                    }
+                    let (value, unit) = if figure.is_scaled() {
+                        let (scale, bonus) = self.find_context_scale(&lines, index);
+                        confidence += bonus;
+                        match scale {
+                            Some(scale) => (
+                                value * scale.factor,
+                                scale.currency.unwrap_or_default().to_owned(),
+                            ),
+                            None => (value, String::new()),
+                        }
+                    } else if *figure == Figure::Employees {
+                        confidence += 0.1;
+                        (value, "FTE".to_owned())
+                    } else {
+                        let currency = self
+                            .find_currency(line)
+                            .or_else(|| self.find_context_scale(&lines, index).0?.currency);
+                        if currency.is_some() {
+                            confidence += 0.15;
+                        }
+                        (value, currency.unwrap_or_default().to_owned())
+                    };
+                    if !is_plausible(*figure, value) {
+                        continue;
+                    }
+                    candidates.push(KeyFigure {
+                        company: report.company.clone(),
+                        year: report.year,
+                        figure: *figure,
+                        value,
+                        unit,
+                        confidence: confidence.min(1.0),
+                        report_type: report.report_type.clone(),
+                        language: report.language.clone(),
+                        page: page_index + 1,
+                        label: label.as_str().to_owned(),
+                        text: line.split_whitespace().collect::<Vec<_>>().join(" "),
+                    });
+                }
+            }
+        }
+        candidates
+    }
+}
+
+fn is_plausible(figure: Figure, value: f64) -> bool {
+    match figure {
+        // Full-time equivalents may be fractional, e.g. 12'345.6
+        Figure::Employees => (1.0..2_000_000.0).contains(&value),
+        Figure::DividendPerShare => value > 0.0 && value < 10_000.0,
+        Figure::NetProfit => value != 0.0,
+        _ => value > 0.0,
+    }
+}
+
+/// Parses numbers like 1'234.5, 1 234,5, 1,234.5 or (123) which is negative
+pub fn parse_number(text: &str) -> Option<f64> {
+    let negative = text.starts_with('(') || text.contains(['-', '–', '−']);
+    let digits: String = text
+        .chars()
+        .filter(|c| c.is_ascii_digit() || *c == '.' || *c == ',')
+        .collect();
+    let normalized = match (digits.rfind('.'), digits.rfind(',')) {
+        // The last separator is the decimal one
+        (Some(dot), Some(comma)) if comma > dot => digits.replace('.', "").replace(',', "."),
+        (Some(_), Some(_)) => digits.replace(',', ""),
+        // A single comma followed by three digits separates thousands
+        (None, Some(comma)) if digits.len() - comma == 4 || digits.matches(',').count() > 1 => {
+            digits.replace(',', "")
+        }
+        (None, Some(_)) => digits.replace(',', "."),
+        (Some(_), None) if digits.matches('.').count() > 1 => digits.replace('.', ""),
+        _ => digits,
+    };
+    let value: f64 = normalized.parse().ok()?;
+    Some(if negative { -value } else { value })
+}
+
+/// Keeps the candidate with the highest confidence per company, year and figure
+pub fn select_best(candidates: Vec<KeyFigure>) -> Vec<KeyFigure> {
+    let mut best: BTreeMap<(String, u16, Figure), KeyFigure> = BTreeMap::new();
+    for candidate in candidates {
+        let key = (candidate.company.clone(), candidate.year, candidate.figure);
+        match best.get(&key) {
+            Some(current) if current.confidence >= candidate.confidence => {}
+            _ => {
+                best.insert(key, candidate);
+            }
+        }
+    }
+    best.into_values().collect()
+}
+
+pub fn write_key_figures_json(path: &Path, figures: &[KeyFigure]) -> Result<(), Box<dyn Error>> {
+    fs::write(path, serde_json::to_string_pretty(figures)?)?;
+    Ok(())
+}
+
+pub fn write_key_figures_csv(path: &Path, figures: &[KeyFigure]) -> Result<(), Box<dyn Error>> {
+    let mut writer = csv::WriterBuilder::new().delimiter(b';').from_path(path)?;
+    for figure in figures {
+        writer.serialize(figure)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_report(language: Language) -> Report {
+        Report {
+            company: "Example".to_owned(),
+            language,
+            report_type: ReportType::AnnualReport,
+            year: 2023,
+            link: "https://example.ch/ar.pdf".to_owned(),
+            alternative_links: Vec::new(),
+        }
+    }
+
+    fn extract(page: &str) -> Vec<KeyFigure> {
+        KeyFigureExtractor::new().extract(&create_report(Language::EN), &[page.to_owned()])
+    }
+
+    fn find(figures: &[KeyFigure], figure: Figure) -> Option<&KeyFigure> {
+        figures.iter().find(|f| f.figure == figure)
+    }
+
+    fn create_candidate(figure: Figure, value: f64, confidence: f32) -> KeyFigure {
+        KeyFigure {
+            company: "Example".to_owned(),
+            year: 2023,
+            figure,
+            value,
+            unit: "CHF".to_owned(),
+            confidence,
+            report_type: ReportType::AnnualReport,
+            language: Language::EN,
+            page: 1,
+            label: String::new(),
+            text: String::new(),
+        }
+    }
+
+    #[test]
+    fn numbers_with_swiss_and_european_separators_are_parsed() {
+        assert_eq!(parse_number("1'234.5"), Some(1234.5));
+        assert_eq!(parse_number("1’234’567"), Some(1234567.0));
+        assert_eq!(parse_number("1.234,5"), Some(1234.5));
+        assert_eq!(parse_number("1,234.5"), Some(1234.5));
+        assert_eq!(parse_number("1\u{202f}234,5"), Some(1234.5));
+        assert_eq!(parse_number("1.234.567"), Some(1234567.0));
+        assert_eq!(parse_number("1,234"), Some(1234.0));
+        assert_eq!(parse_number("12,5"), Some(12.5));
+    }
+
+    #[test]
+    fn parenthesised_and_signed_numbers_are_negative() {
+        assert_eq!(parse_number("(123)"), Some(-123.0));
+        assert_eq!(parse_number("(1'234.5)"), Some(-1234.5));
+        assert_eq!(parse_number("-42"), Some(-42.0));
+        assert_eq!(parse_number("–42"), Some(-42.0));
+        assert_eq!(parse_number("abc"), None);
+    }
+
+    #[test]
+    fn figures_are_scaled_by_the_unit_of_the_table() {
+        let figures = extract("in millions of CHF\nNet revenues 1'234.5 1'100.0\n");
+        let revenue = find(&figures, Figure::Revenue).unwrap();
+        assert_eq!(revenue.value, 1_234_500_000.0);
+        assert_eq!(revenue.unit, "CHF");
+
+        let figures = extract("Mio. CHF\nTotal assets 5'000\n");
+        assert_eq!(
+            find(&figures, Figure::TotalAssets).unwrap().value,
+            5_000_000_000.0
+        );
+
+        let figures = extract("Net income in thousands of USD (2'500)\n");
+        let profit = find(&figures, Figure::NetProfit).unwrap();
+        assert_eq!(profit.value, -2_500_000.0);
+        assert_eq!(profit.unit, "USD");
+    }
+
+    #[test]
+    fn years_are_not_taken_as_values() {
+        let figures = extract("in millions of CHF\nNet revenues 2023 2022\n1'234 1'100\n");
+        assert_eq!(
+            find(&figures, Figure::Revenue).unwrap().value,
+            1_234_000_000.0
+        );
+        let figures = extract("Employees 2023\n");
+        assert!(find(&figures, Figure::Employees).is_none());
+    }
+
+    #[test]
+    fn employees_may_be_fractional() {
+        let figures = extract("Full-time equivalents 12'345.6 FTE\n");
+        let employees = find(&figures, Figure::Employees).unwrap();
+        assert_eq!(employees.value, 12345.6);
+        assert_eq!(employees.unit, "FTE");
+    }
+
+    #[test]
+    fn excluded_lines_are_skipped() {
+        let figures = extract("in millions of CHF\nEquity ratio 45.3%\nRevenue growth 5.1\n");
+        assert!(find(&figures, Figure::Equity).is_none());
+        assert!(find(&figures, Figure::Revenue).is_none());
+    }
+
+    #[test]
+    fn the_most_confident_candidate_is_selected() {
+        let candidates = vec![
+            create_candidate(Figure::Revenue, 1.0, 0.5),
+            create_candidate(Figure::Revenue, 2.0, 0.8),
+            create_candidate(Figure::Revenue, 3.0, 0.8),
+            create_candidate(Figure::Equity, 4.0, 0.3),
+        ];
+        let best = select_best(candidates);
+        assert_eq!(best.len(), 2);
+        // The first of equally confident candidates is kept
+        assert_eq!(find(&best, Figure::Revenue).unwrap().value, 2.0);
+        assert_eq!(find(&best, Figure::Equity).unwrap().value, 4.0);
+    }
+}
@@ -1,3 +1,22 @@
+pub mod charts;
+pub mod checks;
+pub mod coverage;
 pub mod data_structures;
+pub mod diff;
+pub mod downloader;
+pub mod email_security;
+pub mod error;
 pub mod extraction;
+pub mod key_figures;
+pub mod link_check;
+pub mod ocr;
+pub mod output;
+pub mod pdf_metadata;
 pub mod reporting;
+pub mod schema;
+pub mod search;
+pub mod statistics;
+pub mod store;
+pub mod tables;
+pub mod types;
+pub mod validation;
@@ -0,0 +1,194 @@
+use reqwest::header::{CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE};
+use reqwest::{Response, Url};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use crate::data_structures::Report;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LinkStatus {
+    pub report: Report,
+    pub status: Option<u16>,
+    pub final_url: Option<String>,
+    pub content_type: Option<String>,
+    pub content_length: Option<u64>,
+    pub error: Option<String>,
+}
+
+fn get_header(response: &Response, name: reqwest::header::HeaderName) -> Option<String> {
+    response
+        .headers()
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_owned())
+}
+
+fn get_domain(url: &str) -> Option<String> {
+    let url = Url::parse(url).ok()?;
+    let host = url.host_str()?;
+    Some(host.trim_start_matches("www.").to_lowercase())
+}
+
+impl LinkStatus {
+    pub fn from_response(report: Report, response: &Response) -> LinkStatus {
+        // A ranged GET only returns the first byte, the full length is part of the Content-Range
+        let content_length = match get_header(response, CONTENT_RANGE) {
+            Some(range) => range.rsplit('/').next().and_then(|l| l.parse().ok()),
+            None => get_header(response, CONTENT_LENGTH).and_then(|l| l.parse().ok()),
+        };
+        LinkStatus {
+            report,
+            status: Some(response.status().as_u16()),
+            final_url: Some(response.url().to_string()),
+            content_type: get_header(response, CONTENT_TYPE),
+            content_length,
+            error: None,
+        }
+    }
+
+    pub fn from_error(report: Report, error: String) -> LinkStatus {
+        LinkStatus {
+            report,
+            status: None,
+            final_url: None,
+            content_type: None,
+            content_length: None,
+            error: Some(error),
+        }
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.error.is_some() || self.status.is_none_or(|s| s >= 400)
+    }
+
+    pub fn has_moved_domain(&self) -> bool {
+        match &self.final_url {
+            Some(final_url) => get_domain(&self.report.link) != get_domain(final_url),
+            None => false,
+        }
+    }
+
+    pub fn is_html(&self) -> bool {
+        match &self.content_type {
+            Some(content_type) => content_type.to_lowercase().starts_with("text/html"),
+            None => false,
+        }
+    }
+
+    pub fn get_problem(&self) -> Option<String> {
+        if self.is_dead() {
+            match (&self.error, self.status) {
+                (Some(error), _) => Some(format!("Dead link: {}", error)),
+                (None, Some(status)) => Some(format!("Dead link: HTTP status {}", status)),
+                (None, None) => Some("Dead link".to_owned()),
+            }
+        } else if self.has_moved_domain() {
+            Some("Moved to another domain".to_owned())
+        } else if self.is_html() {
+            Some("Returns HTML".to_owned())
+        } else {
+            None
+        }
+    }
+}
+
+pub fn group_problems(statuses: &[LinkStatus]) -> BTreeMap<&str, Vec<&LinkStatus>> {
+    let mut companies: BTreeMap<&str, Vec<&LinkStatus>> = BTreeMap::new();
+    for status in statuses.iter().filter(|s| s.get_problem().is_some()) {
+        companies
+            .entry(&status.report.company)
+            .or_default()
+            .push(status);
+    }
+    companies
+}
+
+pub fn write_link_statuses(path: &Path, statuses: &[LinkStatus]) -> Result<(), Box<dyn Error>> {
+    fs::write(path, serde_json::to_string_pretty(statuses)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_structures::{Language, ReportType};
+    use reqwest::ResponseBuilderExt;
+
+    fn create_report() -> Report {
+        Report {
+            company: "Example".to_owned(),
+            language: Language::EN,
+            report_type: ReportType::AnnualReport,
+            year: 2023,
+            link: "https://www.example.ch/ar.pdf".to_owned(),
+            alternative_links: Vec::new(),
+        }
+    }
+
+    fn create_status(status: u16, final_url: &str, headers: &[(&str, &str)]) -> LinkStatus {
+        let mut builder = http::Response::builder()
+            .status(status)
+            .url(Url::parse(final_url).unwrap());
+        for (name, value) in headers {
+            builder = builder.header(*name, *value);
+        }
+        let response = Response::from(builder.body("").unwrap());
+        LinkStatus::from_response(create_report(), &response)
+    }
+
+    #[test]
+    fn missing_documents_are_dead_links() {
+        for status in [404, 410] {
+            let link = create_status(status, "https://www.example.ch/ar.pdf", &[]);
+            assert!(link.is_dead());
+            assert_eq!(
+                link.get_problem(),
+                Some(format!("Dead link: HTTP status {}", status))
+            );
+        }
+        let link = LinkStatus::from_error(create_report(), "connection refused".to_owned());
+        assert!(link.is_dead());
+        assert_eq!(
+            link.get_problem().as_deref(),
+            Some("Dead link: connection refused")
+        );
+    }
+
+    #[test]
+    fn redirects_are_only_a_problem_if_they_leave_the_domain() {
+        let link = create_status(301, "https://www.example.ch/ar.pdf", &[]);
+        assert!(!link.is_dead());
+        assert_eq!(link.get_problem(), None);
+        // The www prefix and the case of the host don't count as another domain
+        let link = create_status(200, "https://EXAMPLE.ch/reports/ar.pdf", &[]);
+        assert_eq!(link.get_problem(), None);
+        let link = create_status(200, "https://www.example.com/ar.pdf", &[]);
+        assert_eq!(
+            link.get_problem().as_deref(),
+            Some("Moved to another domain")
+        );
+    }
+
+    #[test]
+    fn html_responses_are_found() {
+        let link = create_status(
+            200,
+            "https://www.example.ch/ar.pdf",
+            &[("content-type", "Text/HTML; charset=utf-8")],
+        );
+        assert_eq!(link.get_problem().as_deref(), Some("Returns HTML"));
+        let link = create_status(
+            206,
+            "https://www.example.ch/ar.pdf",
+            &[
+                ("content-type", "application/pdf"),
+                ("content-range", "bytes 0-0/123456"),
+            ],
+        );
+        assert_eq!(link.get_problem(), None);
+        assert_eq!(link.content_length, Some(123456));
+    }
+}
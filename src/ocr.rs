@@ -0,0 +1,128 @@
+use std::error::Error;
+use std::fs;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::types::Language;
+
+pub type OcrError = Box<dyn Error + Send + Sync>;
+
+// Pages are rendered with this resolution before they are passed to tesseract
+const RESOLUTION_DPI: &str = "300";
+
+static NEXT_WORK_DIRECTORY: AtomicUsize = AtomicUsize::new(0);
+
+/// OCR needs tesseract and pdftoppm from poppler to render the pages
+pub fn is_available() -> bool {
+    let has_tesseract = Command::new("tesseract").arg("--version").output().is_ok();
+    let has_pdftoppm = Command::new("pdftoppm").arg("-v").output().is_ok();
+    has_tesseract && has_pdftoppm
+}
+
+pub fn get_tesseract_language(language: &Language) -> &'static str {
+    match language {
+        Language::DE => "deu",
+        Language::FR => "fra",
+        Language::IT => "ita",
+        Language::EN | Language::Other(_) => "eng",
+    }
+}
+
+/// Runs the command in the work directory and kills it after the timeout. The output goes to
+/// files, a full pipe would block the command while it is waited for.
+pub fn run(command: &mut Command, directory: &Path, timeout: Duration) -> Result<(), OcrError> {
+    let log_path = directory.join("stderr.log");
+    let mut child = command
+        .stdout(Stdio::null())
+        .stderr(File::create(&log_path)?)
+        .spawn()?;
+    let start = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if start.elapsed() > timeout {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(format!(
+                "{:?} timed out after {} s",
+                command.get_program(),
+                timeout.as_secs()
+            )
+            .into());
+        }
+        thread::sleep(Duration::from_millis(50));
+    };
+    if !status.success() {
+        let log = fs::read_to_string(&log_path).unwrap_or_default();
+        return Err(format!("{:?} failed: {}", command.get_program(), log.trim()).into());
+    }
+    Ok(())
+}
+
+/// Creates an empty temporary directory, to be removed by the caller
+pub fn create_work_directory() -> Result<PathBuf, OcrError> {
+    let directory = std::env::temp_dir().join(format!(
+        "annual_report_ocr_{}_{}",
+        std::process::id(),
+        NEXT_WORK_DIRECTORY.fetch_add(1, Ordering::SeqCst)
+    ));
+    fs::create_dir_all(&directory)?;
+    Ok(directory)
+}
+
+fn ocr_page_in(
+    directory: &Path,
+    file_path: &Path,
+    page: usize,
+    language: &Language,
+    timeout: Duration,
+) -> Result<String, OcrError> {
+    let page = page.to_string();
+    let image_prefix = directory.join("page");
+    run(
+        Command::new("pdftoppm")
+            .args([
+                "-r",
+                RESOLUTION_DPI,
+                "-f",
+                &page,
+                "-l",
+                &page,
+                "-png",
+                "-singlefile",
+            ])
+            .arg(file_path)
+            .arg(&image_prefix),
+        directory,
+        timeout,
+    )?;
+    // tesseract adds the .txt extension to the output base
+    run(
+        Command::new("tesseract")
+            .arg(image_prefix.with_extension("png"))
+            .arg(&image_prefix)
+            .args(["-l", get_tesseract_language(language)]),
+        directory,
+        timeout,
+    )?;
+    Ok(fs::read_to_string(image_prefix.with_extension("txt"))?)
+}
+
+/// Renders a single page (starting at 1) and recognizes its text. Rendering and recognition are
+/// stopped after the timeout each.
+pub fn ocr_page(
+    file_path: &Path,
+    page: usize,
+    language: &Language,
+    timeout: Duration,
+) -> Result<String, OcrError> {
+    let directory = create_work_directory()?;
+    let result = ocr_page_in(&directory, file_path, page, language, timeout);
+    let _ = fs::remove_dir_all(&directory);
+    result
+}
@@ -0,0 +1,79 @@
+use serde::Serialize;
+use std::fmt;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// One human readable line per record
+    Text,
+    /// One JSON object per line
+    Json,
+    /// CSV with a header and ; as delimiter like the other CSV files
+    Csv,
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            OutputFormat::Text => "text",
+            OutputFormat::Json => "json",
+            OutputFormat::Csv => "csv",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            "csv" => Ok(OutputFormat::Csv),
+            _ => Err(format!(
+                "Unknown output format '{}', expected text, json or csv",
+                name
+            )),
+        }
+    }
+}
+
+/// Writes records to stdout in the given format
+pub fn print_records<T>(format: OutputFormat, records: &[T]) -> io::Result<()>
+where
+    T: Serialize + fmt::Display,
+{
+    let stdout = io::stdout();
+    write_records(stdout.lock(), format, records)
+}
+
+/// Writes records in the given format
+pub fn write_records<W, T>(mut out: W, format: OutputFormat, records: &[T]) -> io::Result<()>
+where
+    W: Write,
+    T: Serialize + fmt::Display,
+{
+    match format {
+        OutputFormat::Text => {
+            for record in records {
+                writeln!(out, "{}", record)?;
+            }
+        }
+        OutputFormat::Json => {
+            for record in records {
+                serde_json::to_writer(&mut out, record)?;
+                writeln!(out)?;
+            }
+        }
+        OutputFormat::Csv => {
+            let mut writer = csv::WriterBuilder::new().delimiter(b';').from_writer(out);
+            for record in records {
+                writer.serialize(record)?;
+            }
+            writer.flush()?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,120 @@
+use chrono::NaiveDate;
+use lopdf::{Document, Object};
+use serde_derive::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct PdfMetadata {
+    pub version: String,
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub creator: Option<String>,
+    pub producer: Option<String>,
+    pub creation_date: Option<String>,
+    pub modification_date: Option<String>,
+    pub encrypted: bool,
+    /// Number of pages with fonts, scanned pages without OCR have none
+    pub text_pages: u32,
+}
+
+impl PdfMetadata {
+    pub fn has_text_layer(&self) -> bool {
+        self.text_pages > 0
+    }
+
+    pub fn get_creation_day(&self) -> Option<NaiveDate> {
+        NaiveDate::parse_from_str(self.creation_date.as_ref()?.get(..10)?, "%Y-%m-%d").ok()
+    }
+}
+
+// Text strings are either UTF-16 with a byte order mark or PDFDocEncoding, which matches
+// Latin-1 for all printable characters
+fn decode_text_string(bytes: &[u8]) -> String {
+    if bytes.starts_with(&[0xfe, 0xff]) {
+        let units: Vec<u16> = bytes[2..]
+            .chunks_exact(2)
+            .map(|c| u16::from_be_bytes([c[0], c[1]]))
+            .collect();
+        String::from_utf16_lossy(&units)
+    } else if bytes.starts_with(&[0xef, 0xbb, 0xbf]) {
+        String::from_utf8_lossy(&bytes[3..]).into_owned()
+    } else {
+        bytes.iter().map(|&b| b as char).collect()
+    }
+}
+
+/// Converts a PDF date like D:20210315123000+01'00' to 2021-03-15T12:30:00+01:00
+pub fn parse_pdf_date(date: &str) -> Option<String> {
+    let date = date.trim().trim_start_matches("D:");
+    let digits: String = date.chars().take_while(|c| c.is_ascii_digit()).collect();
+    if digits.len() < 4 {
+        return None;
+    }
+    let part =
+        |start: usize, default: &'static str| digits.get(start..start + 2).unwrap_or(default);
+    let mut formatted = format!(
+        "{}-{}-{}T{}:{}:{}",
+        &digits[..4],
+        part(4, "01"),
+        part(6, "01"),
+        part(8, "00"),
+        part(10, "00"),
+        part(12, "00")
+    );
+    let zone: String = date[digits.len()..]
+        .chars()
+        .filter(|c| *c != '\'')
+        .collect();
+    match zone.as_str() {
+        "" => {}
+        _ if zone.starts_with('Z') => formatted.push('Z'),
+        _ if zone.len() == 5 && (zone.starts_with('+') || zone.starts_with('-')) => {
+            formatted.push_str(&format!("{}:{}", &zone[..3], &zone[3..]));
+        }
+        _ => {}
+    }
+    Some(formatted)
+}
+
+fn get_info_string(doc: &Document, key: &[u8]) -> Option<String> {
+    let info = match doc.trailer.get(b"Info").ok()? {
+        Object::Reference(id) => doc.get_dictionary(*id).ok()?,
+        Object::Dictionary(info) => info,
+        _ => return None,
+    };
+    let value = match info.get(key).ok()? {
+        Object::Reference(id) => doc.get_object(*id).ok()?,
+        value => value,
+    };
+    match value {
+        Object::String(bytes, _) => {
+            let text = decode_text_string(bytes);
+            let text = text.trim_matches(|c: char| c.is_whitespace() || c == '\0');
+            if text.is_empty() {
+                None
+            } else {
+                Some(text.to_owned())
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Reads the info dictionary and structure of an already decrypted document
+pub fn read_metadata(doc: &Document, encrypted: bool) -> PdfMetadata {
+    let text_pages = doc
+        .get_pages()
+        .values()
+        .filter(|page_id| !doc.get_page_fonts(**page_id).is_empty())
+        .count() as u32;
+    PdfMetadata {
+        version: doc.version.clone(),
+        title: get_info_string(doc, b"Title"),
+        author: get_info_string(doc, b"Author"),
+        creator: get_info_string(doc, b"Creator"),
+        producer: get_info_string(doc, b"Producer"),
+        creation_date: get_info_string(doc, b"CreationDate").and_then(|d| parse_pdf_date(&d)),
+        modification_date: get_info_string(doc, b"ModDate").and_then(|d| parse_pdf_date(&d)),
+        encrypted,
+        text_pages,
+    }
+}
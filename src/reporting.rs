@@ -1,19 +1,29 @@
 use horrorshow::helper::doctype;
-use horrorshow::{box_html, html, RenderMut};
+use horrorshow::{box_html, html, Raw, RenderMut};
+use serde_derive::Serialize;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
-use std::fs::File;
-use std::io::Write;
+use std::path::Path;
 
+use crate::charts::{line_chart, Series};
+use crate::coverage::{write_gaps_csv, Coverage, CoverageStatus, Gap};
 use crate::data_structures::{
-    get_document_name, get_language, CompanyDownloads, CompanyMetadata, Download,
+    filter_companies, CompanyDownloads, CompanyMetadata, Download, Language, ReportType,
 };
+use crate::diff::{Change, ChangeKind};
+use crate::error::{Error, Result};
+use crate::link_check::{group_problems, LinkStatus};
+use crate::statistics::{write_statistics_csv, Statistics, TypeStatistics};
 
-pub fn write_metadata(metadata: &CompanyMetadata) {
-    let filename = format!("metadata/{}.json", &metadata.name);
-    let serialized = serde_json::to_string_pretty(&metadata).unwrap();
+fn write_page(path: &Path, content: &str) -> Result<()> {
+    fs::write(path, format!("{}\n", content)).map_err(Error::io(path))
+}
 
-    fs::write(&filename, serialized)
-        .unwrap_or_else(|_| panic!("Writing file {} failed", &filename));
+// Files next to a page, e.g. the scripts and CSV files it links to
+fn get_directory(page_path: &str) -> &Path {
+    Path::new(page_path)
+        .parent()
+        .unwrap_or_else(|| Path::new(""))
 }
 
 fn get_disclaimer() -> Box<dyn RenderMut> {
@@ -29,7 +39,7 @@ fn get_disclaimer() -> Box<dyn RenderMut> {
             }
             br;
             a (id="warning") {
-                : "Warnings: Occur when documents are missing or not pdf files. Typically the reason is that the document was moved or that you need to approve a disclaimer in order to see it"
+                : "Warnings: Occur when documents are missing, not pdf files, corrupt, encrypted or empty. Typically the reason is that the document was moved or that you need to approve a disclaimer in order to see it"
             }
         }
     }
@@ -45,17 +55,37 @@ fn get_css_style() -> Box<dyn RenderMut> {
     }
 }
 
+fn get_download_label(download: &Download) -> String {
+    let mut label = format!(
+        "{} ({} kB",
+        download.report.report_type.name(&Language::EN),
+        download.size
+    );
+    if download.is_from_fallback() {
+        label.push_str(", from fallback source");
+    }
+    if let Some(warning) = download.get_warning() {
+        label.push_str(&format!(", WARNING: {}", warning));
+    }
+    label.push(')');
+    label
+}
+
+fn get_metadata_field(metadata: &CompanyMetadata, field: &str, value: &str) -> String {
+    if metadata.is_verified(field) {
+        value.to_owned()
+    } else {
+        format!("{} (unverified)", value)
+    }
+}
+
 fn print_reports<'a>(downloads: &'a [&Download]) -> Box<dyn RenderMut + 'a> {
     let target = "_blank";
     box_html! {
         td {
             @ for download in downloads {
-                a (href=&download.report.link, target=&target) {
-                    @ if download.has_warning() {
-                        : format_args!("{} ({} kB, WARNING)", get_document_name(&download.report.report_type), download.size)
-                    } else {
-                        : format_args!("{} ({} kB)", get_document_name(&download.report.report_type), download.size)
-                    }
+                a (href=download.get_source_link(), target=&target) {
+                    : get_download_label(download)
                 }
                 br;
             }
@@ -82,6 +112,125 @@ fn print_sources<'a>(metadata: &'a CompanyMetadata) -> Box<dyn RenderMut + 'a> {
     }
 }
 
+fn get_creation_date(download: &Download, company: &CompanyMetadata) -> String {
+    let date = download
+        .metadata
+        .as_ref()
+        .and_then(|m| m.creation_date.as_deref())
+        .unwrap_or("");
+    if download.is_created_too_early(company) {
+        format!("{} (check year)", date)
+    } else {
+        date.to_owned()
+    }
+}
+
+fn print_document_details<'a>(
+    downloads: &'a [Download],
+    company: &'a CompanyMetadata,
+) -> Box<dyn RenderMut + 'a> {
+    let yes_no = |value: bool| if value { "yes" } else { "no" };
+    box_html! {
+        h2 {
+            : "Documents"
+        }
+        table {
+            tr {
+                th {
+                    : "Year"
+                }
+                th {
+                    : "Document"
+                }
+                th {
+                    : "Language"
+                }
+                th {
+                    : "Pages"
+                }
+                th {
+                    : "Size (kB)"
+                }
+                th {
+                    : "PDF version"
+                }
+                th {
+                    : "Title"
+                }
+                th {
+                    : "Author"
+                }
+                th {
+                    : "Producer"
+                }
+                th {
+                    : "Created"
+                }
+                th {
+                    : "Modified"
+                }
+                th {
+                    : "Encrypted"
+                }
+                th {
+                    : "Text layer"
+                }
+            }
+            @ for download in downloads {
+                tr {
+                    td {
+                        : download.report.year
+                    }
+                    td {
+                        a (href=download.get_source_link(), target="_blank") {
+                            : download.report.report_type.name(&Language::EN)
+                        }
+                    }
+                    td {
+                        : download.report.language.native_name()
+                    }
+                    td {
+                        : download.pages
+                    }
+                    td {
+                        : download.size
+                    }
+                    @ if let Some(metadata) = &download.metadata {
+                        td {
+                            : &metadata.version
+                        }
+                        td {
+                            : metadata.title.as_deref().unwrap_or("")
+                        }
+                        td {
+                            : metadata.author.as_deref().unwrap_or("")
+                        }
+                        td {
+                            : metadata.producer.as_deref().unwrap_or("")
+                        }
+                        td {
+                            : get_creation_date(download, company)
+                        }
+                        td {
+                            : metadata.modification_date.as_deref().unwrap_or("")
+                        }
+                        td {
+                            : yes_no(metadata.encrypted)
+                        }
+                        td {
+                            : format_args!("{} ({} of {} pages)", yes_no(metadata.has_text_layer()), metadata.text_pages, download.pages)
+                        }
+                    } else {
+                        td (colspan="8") {
+                            : download.get_warning().unwrap_or_default()
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
 fn print_html_metadata<'a>(metadata: &'a CompanyMetadata) -> Box<dyn RenderMut + 'a> {
     box_html! {
         meta (name="description", content=format!("Annual reports of {}", metadata.name)) {
@@ -96,17 +245,24 @@ fn print_html_metadata<'a>(metadata: &'a CompanyMetadata) -> Box<dyn RenderMut +
     }
 }
 
-pub fn create_reports(companies: &[CompanyDownloads], tags: &[&str]) {
+pub fn create_reports(
+    companies: &[CompanyDownloads],
+    tags: &[&str],
+    keywords: &HashMap<String, Vec<String>>,
+) -> Result<()> {
     // A silly way to convert the slice to a slice of references
     let all_companies = companies.iter().filter(|_| true).collect();
-    create_index("html/index.html", &all_companies, tags);
+    create_index("html/index.html", &all_companies, tags)?;
+    create_search_page("html/search.html", companies, keywords)?;
+    create_statistics_page("html/statistics.html", companies, tags)?;
+    create_missing_page("html/missing.html", companies)?;
     for company in companies {
-        //write_metadata(&company.company.metadata);
-        create_company_report(company);
+        create_company_report(company)?;
     }
+    Ok(())
 }
 
-pub fn create_index(path: &str, companies: &Vec<&CompanyDownloads>, tags: &[&str]) {
+pub fn create_index(path: &str, companies: &Vec<&CompanyDownloads>, tags: &[&str]) -> Result<()> {
     let (total_documents, total_warnings) = companies.iter().fold((0, 0), |prev, doc| {
         (
             prev.0 + doc.downloads.len(),
@@ -134,6 +290,19 @@ pub fn create_index(path: &str, companies: &Vec<&CompanyDownloads>, tags: &[&str
                     p {
                         : format_args!("In total {} documents of {} companies ({} warnings)", total_documents, companies.len(), total_warnings)
                     }
+                    p {
+                        a (href="search.html") {
+                            : "Search"
+                        }
+                        : " | ";
+                        a (href="statistics.html") {
+                            : "Statistics"
+                        }
+                        : " | ";
+                        a (href="missing.html") {
+                            : "Missing documents"
+                        }
+                    }
                     @ if ! tags.is_empty() {
                         p {
                             : "Sublists: | ";
@@ -177,10 +346,10 @@ pub fn create_index(path: &str, companies: &Vec<&CompanyDownloads>, tags: &[&str
                                     }
                                 }
                                 td {
-                                    : &company_download.company.metadata.country
+                                    : get_metadata_field(&company_download.company.metadata, "country", &company_download.company.metadata.country)
                                 }
                                 td {
-                                    : &company_download.company.metadata.annual_closing_date
+                                    : get_metadata_field(&company_download.company.metadata, "annual_closing_date", &company_download.company.metadata.annual_closing_date)
                                 }
                                 td {
                                     : &company_download.company.reports.len()
@@ -199,11 +368,10 @@ pub fn create_index(path: &str, companies: &Vec<&CompanyDownloads>, tags: &[&str
             }
         }
     );
-    let mut index_file = File::create(path).unwrap();
-    writeln!(index_file, "{}", index_content).unwrap();
+    write_page(Path::new(path), &index_content)
 }
 
-fn create_company_report(company_download: &CompanyDownloads) {
+fn create_company_report(company_download: &CompanyDownloads) -> Result<()> {
     let company = &company_download.company;
 
     let metadata = &company_download.company.metadata;
@@ -241,41 +409,623 @@ fn create_company_report(company_download: &CompanyDownloads) {
                             th {
                                 : "Year"
                             }
+                            @ for language in Language::ALL {
+                                th {
+                                    : language.native_name()
+                                }
+                            }
+                        }
+                        @ for year in (company.oldest_year..=company.newest_year).rev() {
+                            tr {
+                                td {
+                                    : year
+                                }
+                                @ for language in Language::ALL {
+                                    : print_reports(&company_download.get_reports(year, language));
+                                }
+                            }
+                        }
+                    }
+                    : print_coverage(&Coverage::from_company(company));
+                    : print_document_details(&company_download.downloads, metadata);
+                    : print_charts(&Statistics::from_companies(company_name, &[company_download]), "Pages");
+                    : print_sources(metadata);
+                    : get_disclaimer();
+                    a (href="../index.html") {
+                        : "Back"
+                    }
+
+                }
+            }
+        }
+    );
+    write_page(
+        Path::new(&format!("html/companies/{}.html", &company_name)),
+        &index_content,
+    )
+}
+
+pub fn create_changes(path: &str, old_run: &str, new_run: &str, changes: &[Change]) -> Result<()> {
+    let changed: Vec<&Change> = changes
+        .iter()
+        .filter(|c| c.kind != ChangeKind::Unchanged)
+        .collect();
+    let content = format!(
+        "{}",
+        html! {
+            : doctype::HTML;
+            html {
+                head {
+                    : get_css_style();
+                    title : "Changed documents";
+                    meta (charset="UTF-8") {
+
+                    }
+                }
+                body {
+                    a (href="index.html") {
+                        : "Back"
+                    }
+                    h1 {
+                        : "Changed documents"
+                    }
+                    p {
+                        : format_args!("{} of {} documents changed between {} and {}", changed.len(), changes.len(), old_run, new_run)
+                    }
+                    table {
+                        tr {
                             th {
-                                : get_language("EN")
+                                : "Company"
+                            }
+                            th {
+                                : "Year"
+                            }
+                            th {
+                                : "Document"
                             }
                             th {
-                                : get_language("DE")
+                                : "Language"
                             }
                             th {
-                                : get_language("FR")
+                                : "Change"
                             }
                             th {
-                                : get_language("IT")
+                                : "Details"
                             }
                         }
-                        @ for year in (company.oldest_year..=company.newest_year).rev() {
+                        @ for change in &changed {
                             tr {
                                 td {
-                                    : year
+                                    a (href=format_args!("companies/{}.html", change.company)) {
+                                        : &change.company
+                                    }
+                                }
+                                td {
+                                    : change.year
+                                }
+                                td {
+                                    a (href=&change.link, target="_blank") {
+                                        : change.report_type.name(&Language::EN)
+                                    }
+                                }
+                                td {
+                                    : change.language.native_name()
+                                }
+                                td {
+                                    : change.kind.to_string()
+                                }
+                                td {
+                                    : &change.details
                                 }
-                                : print_reports(&company_download.get_reports(year, "EN"));
-                                : print_reports(&company_download.get_reports(year, "DE"));
-                                : print_reports(&company_download.get_reports(year, "FR"));
-                                : print_reports(&company_download.get_reports(year, "IT"));
                             }
                         }
                     }
-                    : print_sources(metadata);
                     : get_disclaimer();
-                    a (href="../index.html") {
+                }
+            }
+        }
+    );
+    write_page(Path::new(path), &content)
+}
+
+pub fn create_link_report(path: &str, statuses: &[LinkStatus]) -> Result<()> {
+    let companies = group_problems(statuses);
+    let content = format!(
+        "{}",
+        html! {
+            : doctype::HTML;
+            html {
+                head {
+                    : get_css_style();
+                    title : "Link check";
+                    meta (charset="UTF-8") {
+
+                    }
+                }
+                body {
+                    a (href="index.html") {
                         : "Back"
                     }
+                    h1 {
+                        : "Link check"
+                    }
+                    p {
+                        : format_args!("{} of {} links have problems", companies.values().map(|s| s.len()).sum::<usize>(), statuses.len())
+                    }
+                    @ for (company, company_statuses) in &companies {
+                        h2 {
+                            a (href=format_args!("companies/{}.html", company)) {
+                                : company
+                            }
+                        }
+                        table {
+                            tr {
+                                th {
+                                    : "Year"
+                                }
+                                th {
+                                    : "Document"
+                                }
+                                th {
+                                    : "Language"
+                                }
+                                th {
+                                    : "Problem"
+                                }
+                                th {
+                                    : "Content type"
+                                }
+                                th {
+                                    : "Final URL"
+                                }
+                            }
+                            @ for status in company_statuses {
+                                tr {
+                                    td {
+                                        : status.report.year
+                                    }
+                                    td {
+                                        a (href=&status.report.link, target="_blank") {
+                                            : status.report.report_type.name(&Language::EN)
+                                        }
+                                    }
+                                    td {
+                                        : status.report.language.native_name()
+                                    }
+                                    td {
+                                        : status.get_problem().unwrap_or_default()
+                                    }
+                                    td {
+                                        : status.content_type.as_deref().unwrap_or("")
+                                    }
+                                    td {
+                                        : status.final_url.as_deref().unwrap_or("")
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    : get_disclaimer();
+                }
+            }
+        }
+    );
+    write_page(Path::new(path), &content)
+}
 
+const SEARCH_SCRIPT: &str = include_str!("../assets/search.js");
+
+// Documents are written as arrays to keep the index small:
+// [year, report type, language, link, keywords]
+type SearchDocument<'a> = (u16, &'a str, &'a str, &'a str, &'a [String]);
+
+#[derive(Serialize)]
+struct SearchCompany<'a> {
+    name: &'a str,
+    country: &'a str,
+    tags: &'a [String],
+    documents: Vec<SearchDocument<'a>>,
+}
+
+#[derive(Serialize)]
+struct SearchIndex<'a> {
+    types: BTreeMap<&'a str, &'a str>,
+    languages: BTreeMap<&'a str, &'a str>,
+    companies: Vec<SearchCompany<'a>>,
+}
+
+fn create_search_index<'a>(
+    companies: &'a [CompanyDownloads],
+    keywords: &'a HashMap<String, Vec<String>>,
+) -> SearchIndex<'a> {
+    let companies = companies
+        .iter()
+        .map(|c| {
+            let metadata = &c.company.metadata;
+            let documents = c
+                .downloads
+                .iter()
+                .map(|d| {
+                    let document_keywords = keywords.get(&d.sha256).map(|k| k.as_slice());
+                    (
+                        d.report.year,
+                        d.report.report_type.code(),
+                        d.report.language.code(),
+                        d.get_source_link(),
+                        document_keywords.unwrap_or_default(),
+                    )
+                })
+                .collect();
+            SearchCompany {
+                name: &metadata.name,
+                country: &metadata.country,
+                tags: &metadata.tags,
+                documents,
+            }
+        })
+        .collect();
+    SearchIndex {
+        types: ReportType::ALL
+            .iter()
+            .map(|t| (t.code(), t.name(&Language::EN)))
+            .collect(),
+        languages: Language::ALL
+            .iter()
+            .map(|l| (l.code(), l.native_name()))
+            .collect(),
+        companies,
+    }
+}
+
+fn print_select<'a>(name: &'a str, label: &'a str) -> Box<dyn RenderMut + 'a> {
+    box_html! {
+        label {
+            : label;
+            : " ";
+            select (name=name) {
+                option (value="") {
+                    : "All"
+                }
+            }
+        }
+        : " "
+    }
+}
+
+/// Static search page, the index is loaded from search-index.js next to it so that the page
+/// also works when opened from the file system
+pub fn create_search_page(
+    path: &str,
+    companies: &[CompanyDownloads],
+    keywords: &HashMap<String, Vec<String>>,
+) -> Result<()> {
+    let search_index = create_search_index(companies, keywords);
+    let directory = get_directory(path);
+    let index_path = directory.join("search-index.js");
+    let serialized = serde_json::to_string(&search_index).map_err(Error::json(&index_path))?;
+    write_page(
+        &index_path,
+        &format!("window.SEARCH_INDEX = {};", serialized),
+    )?;
+    write_page(&directory.join("search.js"), SEARCH_SCRIPT.trim_end())?;
+
+    let content = format!(
+        "{}",
+        html! {
+            : doctype::HTML;
+            html {
+                head {
+                    : get_css_style();
+                    title : "Search the annual report database";
+                    meta (charset="UTF-8") {
+
+                    }
+                }
+                body {
+                    a (href="index.html") {
+                        : "Back"
+                    }
+                    h1 {
+                        : "Search"
+                    }
+                    form (id="search") {
+                        p {
+                            input (type="search", name="query", placeholder="Company, tag or keyword", size="40") {
+
+                            }
+                        }
+                        p {
+                            : print_select("tag", "Tag");
+                            : print_select("from_year", "From");
+                            : print_select("to_year", "To");
+                            : print_select("report_type", "Document");
+                            : print_select("language", "Language");
+                        }
+                    }
+                    p (id="summary") {
+
+                    }
+                    table {
+                        thead {
+                            tr {
+                                th {
+                                    : "Company"
+                                }
+                                th {
+                                    : "Year"
+                                }
+                                th {
+                                    : "Document"
+                                }
+                                th {
+                                    : "Language"
+                                }
+                                th {
+                                    : "Tags"
+                                }
+                            }
+                        }
+                        tbody (id="results") {
+
+                        }
+                    }
+                    : get_disclaimer();
+                    script (src="search-index.js") {
+
+                    }
+                    script (src="search.js") {
+
+                    }
+                }
+            }
+        }
+    );
+    write_page(Path::new(path), &content)
+}
+
+fn get_chart(statistics: &Statistics, title: &str, value: fn(&TypeStatistics) -> f64) -> String {
+    let series: Vec<Series> = statistics
+        .get_report_types()
+        .into_iter()
+        .map(|t| Series {
+            label: t.name(&Language::EN),
+            points: statistics.get_series(t, value),
+        })
+        .collect();
+    line_chart(title, &series)
+}
+
+fn print_charts<'a>(statistics: &'a Statistics, pages_title: &'a str) -> Box<dyn RenderMut + 'a> {
+    box_html! {
+        @ if !statistics.years.is_empty() {
+            h2 {
+                : "Statistics"
+            }
+            p {
+                : Raw(get_chart(statistics, pages_title, |s| s.get_average_pages()));
+                : Raw(get_chart(statistics, "Size (MB)", |s| s.get_average_size_kb() / 1024.0));
+                : Raw(get_chart(statistics, "Documents", |s| s.documents as f64));
+            }
+        }
+    }
+}
+
+fn print_statistics_table<'a>(statistics: &'a Statistics) -> Box<dyn RenderMut + 'a> {
+    let report_types = statistics.get_report_types();
+    box_html! {
+        table {
+            tr {
+                th {
+                    : "Year"
+                }
+                @ for report_type in &report_types {
+                    th {
+                        : format_args!("{} (documents / average pages / average kB)", report_type.name(&Language::EN))
+                    }
+                }
+            }
+            @ for year in statistics.years.keys().rev() {
+                tr {
+                    td {
+                        : year
+                    }
+                    @ for report_type in &report_types {
+                        td {
+                            @ if let Some(s) = statistics.get(*year, report_type) {
+                                : format_args!("{} / {:.0} / {:.0}", s.documents, s.get_average_pages(), s.get_average_size_kb())
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Page and size statistics of all companies and of every tag, also written to statistics.csv
+pub fn create_statistics_page(
+    path: &str,
+    companies: &[CompanyDownloads],
+    tags: &[&str],
+) -> Result<()> {
+    let mut groups = vec![Statistics::from_companies(
+        "All companies",
+        &companies.iter().collect::<Vec<_>>(),
+    )];
+    for tag in tags {
+        groups.push(Statistics::from_companies(
+            tag,
+            &filter_companies(tag, companies),
+        ));
+    }
+    write_statistics_csv(&get_directory(path).join("statistics.csv"), &groups)?;
+
+    let content = format!(
+        "{}",
+        html! {
+            : doctype::HTML;
+            html {
+                head {
+                    : get_css_style();
+                    title : "Statistics of the annual report database";
+                    meta (charset="UTF-8") {
+
+                    }
+                }
+                body {
+                    a (href="index.html") {
+                        : "Back"
+                    }
+                    h1 {
+                        : "Statistics"
+                    }
+                    p {
+                        : "Pages and sizes are averages per report, translations of a report are counted once. ";
+                        a (href="statistics.csv") {
+                            : "Download as CSV"
+                        }
+                    }
+                    @ for group in &groups {
+                        h1 {
+                            : format_args!("{} ({} companies, {} documents)", group.name, group.companies, group.get_documents())
+                        }
+                        : print_charts(group, "Average pages");
+                        : print_statistics_table(group);
+                    }
+                    : get_disclaimer();
+                }
+            }
+        }
+    );
+    write_page(Path::new(path), &content)
+}
+
+fn print_coverage<'a>(coverage: &'a Coverage) -> Box<dyn RenderMut + 'a> {
+    box_html! {
+        h2 {
+            : "Coverage"
+        }
+        table {
+            tr {
+                th {
+                    : "Year"
+                }
+                @ for (report_type, language) in coverage.documents.keys() {
+                    th {
+                        : format_args!("{}-{}", report_type, language)
+                    }
+                }
+            }
+            @ for year in (coverage.oldest_year..=coverage.newest_year).rev() {
+                tr {
+                    td {
+                        : year
+                    }
+                    @ for (report_type, language) in coverage.documents.keys() {
+                        td {
+                            : match coverage.get_status(year, report_type, language) {
+                                CoverageStatus::Present => "yes",
+                                CoverageStatus::Missing => "MISSING",
+                                CoverageStatus::NotExpected => "",
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        @ if !coverage.gaps.is_empty() {
+            ul {
+                @ for gap in &coverage.gaps {
+                    li {
+                        : gap.to_string()
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Documents that are expected from the history of each company but not in the sources, also
+/// written to missing.csv as a work list
+pub fn create_missing_page(path: &str, companies: &[CompanyDownloads]) -> Result<()> {
+    let coverages: Vec<(Coverage, &CompanyMetadata)> = companies
+        .iter()
+        .map(|c| (Coverage::from_company(&c.company), &c.company.metadata))
+        .collect();
+    let gaps: Vec<(&Gap, &str)> = coverages
+        .iter()
+        .flat_map(|(coverage, metadata)| {
+            coverage
+                .gaps
+                .iter()
+                .map(move |g| (g, metadata.url.as_str()))
+        })
+        .collect();
+    write_gaps_csv(&get_directory(path).join("missing.csv"), &gaps)?;
+
+    let content = format!(
+        "{}",
+        html! {
+            : doctype::HTML;
+            html {
+                head {
+                    : get_css_style();
+                    title : "Missing documents in the annual report database";
+                    meta (charset="UTF-8") {
+
+                    }
+                }
+                body {
+                    a (href="index.html") {
+                        : "Back"
+                    }
+                    h1 {
+                        : "Missing documents"
+                    }
+                    p {
+                        : format_args!("{} gaps in the documents of {} companies. A document is expected from the first year it was published until the newest year of the company. ", gaps.len(), companies.len());
+                        a (href="missing.csv") {
+                            : "Download as CSV"
+                        }
+                    }
+                    table {
+                        tr {
+                            th {
+                                : "Company"
+                            }
+                            th {
+                                : "Document"
+                            }
+                            th {
+                                : "Language"
+                            }
+                            th {
+                                : "Gap"
+                            }
+                        }
+                        @ for (gap, _) in &gaps {
+                            tr {
+                                td {
+                                    a (href=format_args!("companies/{}.html", gap.company)) {
+                                        : &gap.company
+                                    }
+                                }
+                                td {
+                                    : gap.report_type.name(&Language::EN)
+                                }
+                                td {
+                                    : gap.language.native_name()
+                                }
+                                td {
+                                    : gap.to_string()
+                                }
+                            }
+                        }
+                    }
+                    : get_disclaimer();
                 }
             }
         }
     );
-    let mut index_file = File::create(format!("html/companies/{}.html", &company_name)).unwrap();
-    writeln!(index_file, "{}", index_content).unwrap();
+    write_page(Path::new(path), &content)
 }
@@ -0,0 +1,314 @@
+use chrono::{Datelike, Utc};
+use reqwest::Url;
+use serde_derive::Serialize;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::fs;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use crate::data_structures::{CompanyMetadata, Report};
+
+const OLDEST_YEAR: u16 = 1900;
+
+#[derive(Debug, Serialize)]
+pub struct Problem {
+    pub file: PathBuf,
+    pub line: Option<u64>,
+    pub message: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{}: {}", self.file.display(), line, self.message),
+            None => write!(f, "{}: {}", self.file.display(), self.message),
+        }
+    }
+}
+
+struct ProblemList<'a> {
+    file: &'a Path,
+    problems: &'a mut Vec<Problem>,
+}
+
+impl ProblemList<'_> {
+    fn add(&mut self, line: Option<u64>, message: String) {
+        self.problems.push(Problem {
+            file: self.file.to_path_buf(),
+            line,
+            message,
+        });
+    }
+}
+
+fn check_link(link: &str) -> Option<String> {
+    match Url::parse(link) {
+        Ok(url) if url.scheme() == "http" || url.scheme() == "https" => None,
+        Ok(url) => Some(format!(
+            "Unsupported scheme '{}' in link {}",
+            url.scheme(),
+            link
+        )),
+        Err(e) => Some(format!("Malformed link '{}': {}", link, e)),
+    }
+}
+
+fn check_report(report: &Report, line: Option<u64>, problems: &mut ProblemList) {
+    if !report.report_type.is_known() {
+        problems.add(
+            line,
+            format!("Unknown report type '{}'", report.report_type),
+        );
+    }
+    if !report.language.is_known() {
+        problems.add(line, format!("Unknown language '{}'", report.language));
+    }
+    let newest_year = Utc::now().year() as u16 + 1;
+    if report.year < OLDEST_YEAR || report.year > newest_year {
+        problems.add(line, format!("Implausible year {}", report.year));
+    }
+    for link in report.get_links() {
+        if let Some(message) = check_link(link) {
+            problems.add(line, message);
+        }
+    }
+}
+
+pub fn check_source_file(source_file: &Path, problems: &mut Vec<Problem>) {
+    let mut problems = ProblemList {
+        file: source_file,
+        problems,
+    };
+    let company = source_file
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let file = match File::open(source_file) {
+        Ok(file) => file,
+        Err(e) => {
+            problems.add(None, format!("Reading file failed: {}", e));
+            return;
+        }
+    };
+    let mut rdr = csv::ReaderBuilder::new().delimiter(b';').from_reader(file);
+    let headers = match rdr.headers() {
+        Ok(headers) => headers.clone(),
+        Err(e) => {
+            problems.add(Some(1), format!("Reading header failed: {}", e));
+            return;
+        }
+    };
+    let mut keys = HashMap::new();
+    let mut mismatching_companies = BTreeSet::new();
+    for record in rdr.records() {
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                let line = e.position().map(|p| p.line());
+                problems.add(line, format!("Malformed row: {}", e));
+                continue;
+            }
+        };
+        let line = record.position().map(|p| p.line());
+        let report: Report = match record.deserialize(Some(&headers)) {
+            Ok(report) => report,
+            Err(e) => {
+                problems.add(line, format!("Invalid row: {}", e));
+                continue;
+            }
+        };
+        check_report(&report, line, &mut problems);
+        // Only report the first row of every mismatching company name
+        if report.company != company && mismatching_companies.insert(report.company.clone()) {
+            problems.add(
+                line,
+                format!(
+                    "Company '{}' does not match the file name '{}'",
+                    report.company, company
+                ),
+            );
+        }
+
+        let key = (
+            report.company.clone(),
+            report.year,
+            report.report_type.clone(),
+            report.language.clone(),
+        );
+        if let Some(first_line) = keys.insert(key, line) {
+            problems.add(
+                line,
+                format!(
+                    "Duplicate entry for {} {} {}-{} (first on line {})",
+                    report.company,
+                    report.year,
+                    report.report_type,
+                    report.language,
+                    first_line.unwrap_or_default()
+                ),
+            );
+        }
+    }
+}
+
+pub fn check_metadata_file(metadata_file: &Path, problems: &mut Vec<Problem>) {
+    let mut problems = ProblemList {
+        file: metadata_file,
+        problems,
+    };
+    let contents = match fs::read_to_string(metadata_file) {
+        Ok(contents) => contents,
+        Err(e) => {
+            problems.add(None, format!("Reading file failed: {}", e));
+            return;
+        }
+    };
+    match serde_json::from_str::<CompanyMetadata>(&contents) {
+        Ok(metadata) => {
+            let name = metadata_file
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_default();
+            if metadata.name != name {
+                problems.add(
+                    None,
+                    format!(
+                        "Name '{}' does not match the file name '{}'",
+                        metadata.name, name
+                    ),
+                );
+            }
+            if !metadata.url.is_empty() {
+                if let Some(message) = check_link(&metadata.url) {
+                    problems.add(None, message);
+                }
+            }
+        }
+        Err(e) => problems.add(Some(e.line() as u64), format!("Invalid metadata: {}", e)),
+    }
+}
+
+fn get_files(directory: &Path, extension: &str, problems: &mut Vec<Problem>) -> Vec<PathBuf> {
+    let entries = match fs::read_dir(directory) {
+        Ok(entries) => entries,
+        Err(e) => {
+            problems.push(Problem {
+                file: directory.to_path_buf(),
+                line: None,
+                message: format!("Reading directory failed: {}", e),
+            });
+            return Vec::new();
+        }
+    };
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|e| e == extension))
+        .collect();
+    files.sort();
+    files
+}
+
+pub fn check_all(source_directory: &Path, metadata_directory: &Path) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    let source_files = get_files(source_directory, "csv", &mut problems);
+    let metadata_files = get_files(metadata_directory, "json", &mut problems);
+
+    for source_file in &source_files {
+        check_source_file(source_file, &mut problems);
+    }
+    for metadata_file in &metadata_files {
+        check_metadata_file(metadata_file, &mut problems);
+    }
+
+    let metadata_names: BTreeSet<_> = metadata_files
+        .iter()
+        .filter_map(|f| f.file_stem())
+        .collect();
+    for source_file in &source_files {
+        if let Some(name) = source_file.file_stem() {
+            if !metadata_names.contains(name) {
+                problems.push(Problem {
+                    file: source_file.clone(),
+                    line: None,
+                    message: format!(
+                        "No metadata file {}",
+                        metadata_directory
+                            .join(format!("{}.json", name.to_string_lossy()))
+                            .display()
+                    ),
+                });
+            }
+        }
+    }
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check_source(name: &str, contents: &str) -> Vec<(Option<u64>, String)> {
+        let directory = std::env::temp_dir().join("annual_report_database_schema");
+        fs::create_dir_all(&directory).unwrap();
+        let source_file = directory.join(format!("{}.csv", name));
+        fs::write(&source_file, contents).unwrap();
+        let mut problems = Vec::new();
+        check_source_file(&source_file, &mut problems);
+        problems.into_iter().map(|p| (p.line, p.message)).collect()
+    }
+
+    #[test]
+    fn invalid_rows_are_reported_with_their_line() {
+        let problems = check_source(
+            "Example",
+            "company;language;report_type;year;link\n\
+             Example;EN;AR;2020;https://example.ch/ar.pdf\n\
+             Example;EN;AR;twenty;https://example.ch/ar.pdf\n\
+             Example;XX;YY;1800;https://example.ch/ar.pdf\n\
+             Example;DE;AR;2020;ftp://example.ch/ar.pdf\n\
+             Example;FR;AR;2020;not a link\n\
+             Example;EN;AR;2020;https://example.ch/ar-2.pdf\n\
+             Other;EN;AR;2021;https://example.ch/ar.pdf\n\
+             Example;EN;AR\n",
+        );
+        let lines: Vec<_> = problems.iter().map(|(line, _)| *line).collect();
+        assert_eq!(
+            lines,
+            [3, 4, 4, 4, 5, 6, 7, 8, 9]
+                .iter()
+                .map(|l| Some(*l))
+                .collect::<Vec<_>>()
+        );
+        let messages: Vec<_> = problems
+            .iter()
+            .map(|(_, message)| message.as_str())
+            .collect();
+        assert!(messages[0].starts_with("Invalid row: "));
+        assert_eq!(
+            &messages[1..8],
+            [
+                "Unknown report type 'YY'",
+                "Unknown language 'XX'",
+                "Implausible year 1800",
+                "Unsupported scheme 'ftp' in link ftp://example.ch/ar.pdf",
+                "Malformed link 'not a link': relative URL without a base",
+                "Duplicate entry for Example 2020 AR-EN (first on line 2)",
+                "Company 'Other' does not match the file name 'Example'",
+            ]
+        );
+        assert!(messages[8].starts_with("Malformed row: "));
+    }
+
+    #[test]
+    fn a_valid_source_has_no_problems() {
+        let problems = check_source(
+            "Valid",
+            "company;language;report_type;year;link\n\
+             Valid;EN;AR;2020;https://example.ch/ar.pdf\n\
+             Valid;DE;AR;2020;https://example.ch/gb.pdf\n",
+        );
+        assert!(problems.is_empty(), "{:?}", problems);
+    }
+}
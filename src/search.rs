@@ -0,0 +1,293 @@
+use serde_derive::Serialize;
+use std::fmt;
+use std::fs;
+use std::ops::Bound;
+use std::path::{Path, PathBuf};
+
+use tantivy::collector::TopDocs;
+use tantivy::query::{BooleanQuery, Occur, Query, QueryParser, RangeQuery, TermQuery};
+use tantivy::schema::{
+    Field, IndexRecordOption, Schema, TantivyDocument, Value, FAST, INDEXED, STORED, STRING, TEXT,
+};
+use tantivy::snippet::SnippetGenerator;
+use tantivy::{Index, IndexWriter, Term};
+
+use crate::data_structures::{Language, Report, ReportFilter, ReportType};
+
+const WRITER_MEMORY: usize = 100_000_000;
+const SNIPPET_LENGTH: usize = 200;
+
+#[derive(Clone, Copy)]
+struct Fields {
+    company: Field,
+    tag: Field,
+    year: Field,
+    report_type: Field,
+    language: Field,
+    page: Field,
+    text: Field,
+}
+
+impl Fields {
+    fn create_schema() -> (Schema, Fields) {
+        let mut builder = Schema::builder();
+        let fields = Fields {
+            company: builder.add_text_field("company", STRING | STORED),
+            tag: builder.add_text_field("tag", STRING | STORED),
+            year: builder.add_u64_field("year", INDEXED | STORED | FAST),
+            report_type: builder.add_text_field("report_type", STRING | STORED),
+            language: builder.add_text_field("language", STRING | STORED),
+            page: builder.add_u64_field("page", STORED),
+            text: builder.add_text_field("text", TEXT | STORED),
+        };
+        (builder.build(), fields)
+    }
+
+    fn from_schema(schema: &Schema) -> tantivy::Result<Fields> {
+        Ok(Fields {
+            company: schema.get_field("company")?,
+            tag: schema.get_field("tag")?,
+            year: schema.get_field("year")?,
+            report_type: schema.get_field("report_type")?,
+            language: schema.get_field("language")?,
+            page: schema.get_field("page")?,
+            text: schema.get_field("text")?,
+        })
+    }
+}
+
+pub fn get_index_path(download_directory: &Path) -> PathBuf {
+    download_directory.join("index")
+}
+
+pub struct IndexBuilder {
+    writer: IndexWriter,
+    fields: Fields,
+}
+
+impl IndexBuilder {
+    /// Creates a new empty index, an existing index in the directory is replaced
+    pub fn create(path: &Path) -> tantivy::Result<IndexBuilder> {
+        if path.exists() {
+            fs::remove_dir_all(path)?;
+        }
+        fs::create_dir_all(path)?;
+        let (schema, fields) = Fields::create_schema();
+        let index = Index::create_in_dir(path, schema)?;
+        Ok(IndexBuilder {
+            writer: index.writer(WRITER_MEMORY)?,
+            fields,
+        })
+    }
+
+    /// Adds every page of a report as a separate document
+    pub fn add_report(
+        &mut self,
+        report: &Report,
+        tags: &[String],
+        pages: &[String],
+    ) -> tantivy::Result<()> {
+        let f = self.fields;
+        for (index, text) in pages.iter().enumerate() {
+            if text.trim().is_empty() {
+                continue;
+            }
+            let mut doc = TantivyDocument::default();
+            doc.add_text(f.company, &report.company);
+            for tag in tags {
+                doc.add_text(f.tag, tag);
+            }
+            doc.add_u64(f.year, report.year as u64);
+            doc.add_text(f.report_type, report.report_type.code());
+            doc.add_text(f.language, report.language.code());
+            doc.add_u64(f.page, index as u64 + 1);
+            doc.add_text(f.text, text);
+            self.writer.add_document(doc)?;
+        }
+        Ok(())
+    }
+
+    pub fn commit(mut self) -> tantivy::Result<()> {
+        self.writer.commit()?;
+        self.writer.wait_merging_threads()
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Hit {
+    pub company: String,
+    pub year: u16,
+    pub report_type: ReportType,
+    pub language: Language,
+    pub page: u64,
+    pub score: f32,
+    pub snippet: String,
+}
+
+impl fmt::Display for Hit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{}, {}, {}-{}, page {} ({:.2})",
+            self.company, self.year, self.report_type, self.language, self.page, self.score
+        )?;
+        write!(f, "    {}", self.snippet)
+    }
+}
+
+pub struct SearchIndex {
+    index: Index,
+    fields: Fields,
+}
+
+fn term_query(field: Field, value: &str) -> Box<dyn Query> {
+    Box::new(TermQuery::new(
+        Term::from_field_text(field, value),
+        IndexRecordOption::Basic,
+    ))
+}
+
+// Matches documents with any of the given values, or every document if there are none
+fn any_of(field: Field, values: &[String]) -> Option<Box<dyn Query>> {
+    if values.is_empty() {
+        return None;
+    }
+    let queries = values
+        .iter()
+        .map(|v| (Occur::Should, term_query(field, v)))
+        .collect();
+    Some(Box::new(BooleanQuery::new(queries)))
+}
+
+fn get_text(doc: &TantivyDocument, field: Field) -> String {
+    doc.get_first(field)
+        .and_then(|v| v.as_str())
+        .unwrap_or_default()
+        .to_owned()
+}
+
+fn get_number(doc: &TantivyDocument, field: Field) -> u64 {
+    doc.get_first(field).and_then(|v| v.as_u64()).unwrap_or(0)
+}
+
+impl SearchIndex {
+    pub fn open(path: &Path) -> tantivy::Result<SearchIndex> {
+        let index = Index::open_in_dir(path)?;
+        let fields = Fields::from_schema(&index.schema())?;
+        Ok(SearchIndex { index, fields })
+    }
+
+    // The index has no case insensitive regex support, so the matching company names are
+    // looked up in the term dictionary instead
+    fn get_matching_companies(&self, filter: &ReportFilter) -> tantivy::Result<Vec<String>> {
+        let regex = match &filter.company {
+            Some(regex) => regex,
+            None => return Ok(Vec::new()),
+        };
+        let searcher = self.index.reader()?.searcher();
+        let mut companies = Vec::new();
+        for segment_reader in searcher.segment_readers() {
+            let inverted_index = segment_reader.inverted_index(self.fields.company)?;
+            let mut terms = inverted_index.terms().stream()?;
+            while terms.advance() {
+                let company = String::from_utf8_lossy(terms.key()).into_owned();
+                if regex.is_match(&company) && !companies.contains(&company) {
+                    companies.push(company);
+                }
+            }
+        }
+        Ok(companies)
+    }
+
+    fn create_filter_queries(
+        &self,
+        filter: &ReportFilter,
+    ) -> tantivy::Result<Vec<(Occur, Box<dyn Query>)>> {
+        let f = self.fields;
+        let mut queries = Vec::new();
+        if filter.company.is_some() {
+            let companies = self.get_matching_companies(filter)?;
+            // Without any matching company the empty query matches nothing
+            let query = any_of(f.company, &companies)
+                .unwrap_or_else(|| Box::new(BooleanQuery::new(Vec::new())));
+            queries.push((Occur::Must, query));
+        }
+        let report_types: Vec<String> = filter.report_types.iter().map(|t| t.to_string()).collect();
+        let languages: Vec<String> = filter.languages.iter().map(|l| l.to_string()).collect();
+        let term_filters = vec![
+            any_of(f.tag, &filter.tags),
+            any_of(f.report_type, &report_types),
+            any_of(f.language, &languages),
+        ];
+        queries.extend(term_filters.into_iter().flatten().map(|q| (Occur::Must, q)));
+        if filter.from_year.is_some() || filter.to_year.is_some() {
+            let bound = |year: Option<u16>| match year {
+                Some(year) => Bound::Included(year as u64),
+                None => Bound::Unbounded,
+            };
+            let range = RangeQuery::new_u64_bounds(
+                "year".to_owned(),
+                bound(filter.from_year),
+                bound(filter.to_year),
+            );
+            queries.push((Occur::Must, Box::new(range)));
+        }
+        Ok(queries)
+    }
+
+    /// Returns the best matching pages, the query supports the tantivy query syntax
+    pub fn search(
+        &self,
+        query: &str,
+        filter: &ReportFilter,
+        limit: usize,
+    ) -> tantivy::Result<Vec<Hit>> {
+        let f = self.fields;
+        let mut parser = QueryParser::for_index(&self.index, vec![f.text]);
+        parser.set_conjunction_by_default();
+        let text_query = parser.parse_query(query)?;
+
+        let mut queries = self.create_filter_queries(filter)?;
+        queries.push((Occur::Must, text_query.box_clone()));
+        let query = BooleanQuery::new(queries);
+
+        let searcher = self.index.reader()?.searcher();
+        let mut snippet_generator = SnippetGenerator::create(&searcher, &*text_query, f.text)?;
+        snippet_generator.set_max_num_chars(SNIPPET_LENGTH);
+
+        let mut hits = Vec::new();
+        for (score, address) in searcher.search(&query, &TopDocs::with_limit(limit))? {
+            let doc: TantivyDocument = searcher.doc(address)?;
+            let snippet = snippet_generator.snippet_from_doc(&doc);
+            hits.push(Hit {
+                company: get_text(&doc, f.company),
+                year: get_number(&doc, f.year) as u16,
+                report_type: ReportType::from_code(&get_text(&doc, f.report_type)),
+                language: Language::from_code(&get_text(&doc, f.language)),
+                page: get_number(&doc, f.page),
+                score,
+                snippet: get_snippet_text(&snippet),
+            });
+        }
+        Ok(hits)
+    }
+}
+
+// Plain text version of the snippet with the matches marked and the whitespace collapsed
+fn get_snippet_text(snippet: &tantivy::snippet::Snippet) -> String {
+    let fragment = snippet.fragment();
+    let mut text = String::new();
+    let mut start = 0;
+    for range in snippet.highlighted() {
+        if range.start < start {
+            continue;
+        }
+        text.push_str(&fragment[start..range.start]);
+        text.push_str("**");
+        text.push_str(&fragment[range.clone()]);
+        text.push_str("**");
+        start = range.end;
+    }
+    text.push_str(&fragment[start..]);
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
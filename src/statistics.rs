@@ -0,0 +1,158 @@
+use serde_derive::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::data_structures::{CompanyDownloads, Download, ReportType};
+use crate::error::{Error, Result};
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct TypeStatistics {
+    /// Valid documents in all languages
+    pub documents: usize,
+    /// Reports counted once, independent of the number of languages
+    pub reports: usize,
+    pub pages: u64,
+    pub size_kb: u64,
+}
+
+impl TypeStatistics {
+    pub fn get_average_pages(&self) -> f64 {
+        match self.reports {
+            0 => 0.0,
+            reports => self.pages as f64 / reports as f64,
+        }
+    }
+
+    pub fn get_average_size_kb(&self) -> f64 {
+        match self.reports {
+            0 => 0.0,
+            reports => self.size_kb as f64 / reports as f64,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct Statistics {
+    pub name: String,
+    pub companies: usize,
+    pub years: BTreeMap<u16, BTreeMap<ReportType, TypeStatistics>>,
+}
+
+impl Statistics {
+    pub fn from_companies(name: &str, companies: &[&CompanyDownloads]) -> Statistics {
+        let mut statistics = Statistics {
+            name: name.to_owned(),
+            companies: companies.len(),
+            ..Default::default()
+        };
+        for company in companies {
+            statistics.add_downloads(&company.downloads);
+        }
+        statistics
+    }
+
+    // The translations of a report usually have the same length, so the length of a report is
+    // taken from its longest language version
+    fn add_downloads(&mut self, downloads: &[Download]) {
+        let mut reports: BTreeMap<(u16, &ReportType), &Download> = BTreeMap::new();
+        for download in downloads.iter().filter(|d| d.validation.is_valid()) {
+            self.get_entry(download.report.year, &download.report.report_type)
+                .documents += 1;
+            let key = (download.report.year, &download.report.report_type);
+            match reports.get(&key) {
+                Some(longest) if longest.pages >= download.pages => {}
+                _ => {
+                    reports.insert(key, download);
+                }
+            }
+        }
+        for ((year, report_type), download) in reports {
+            let entry = self.get_entry(year, report_type);
+            entry.reports += 1;
+            entry.pages += u64::from(download.pages);
+            entry.size_kb += download.size;
+        }
+    }
+
+    fn get_entry(&mut self, year: u16, report_type: &ReportType) -> &mut TypeStatistics {
+        self.years
+            .entry(year)
+            .or_default()
+            .entry(report_type.clone())
+            .or_default()
+    }
+
+    pub fn get(&self, year: u16, report_type: &ReportType) -> Option<&TypeStatistics> {
+        self.years.get(&year)?.get(report_type)
+    }
+
+    /// Report types with at least one document, in the order of ReportType::ALL
+    pub fn get_report_types(&self) -> Vec<&ReportType> {
+        let mut types: Vec<&ReportType> = self.years.values().flat_map(|t| t.keys()).collect();
+        types.sort();
+        types.dedup();
+        types
+    }
+
+    /// Values per year for one report type, e.g. to draw a chart
+    pub fn get_series<F>(&self, report_type: &ReportType, value: F) -> Vec<(u16, f64)>
+    where
+        F: Fn(&TypeStatistics) -> f64,
+    {
+        self.years
+            .iter()
+            .filter_map(|(year, types)| Some((*year, value(types.get(report_type)?))))
+            .collect()
+    }
+
+    pub fn get_documents(&self) -> usize {
+        self.years
+            .values()
+            .flat_map(|t| t.values())
+            .map(|s| s.documents)
+            .sum()
+    }
+}
+
+#[derive(Serialize)]
+struct StatisticsRow<'a> {
+    group: &'a str,
+    year: u16,
+    report_type: &'a ReportType,
+    companies: usize,
+    documents: usize,
+    reports: usize,
+    pages: u64,
+    size_kb: u64,
+    average_pages: String,
+    average_size_kb: String,
+}
+
+/// Writes one row per group, year and report type, e.g. to continue the analysis in a spreadsheet
+pub fn write_statistics_csv(path: &Path, statistics: &[Statistics]) -> Result<()> {
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(b';')
+        .from_path(path)
+        .map_err(Error::csv(path))?;
+    for group in statistics {
+        for (year, types) in &group.years {
+            for (report_type, s) in types {
+                writer
+                    .serialize(StatisticsRow {
+                        group: &group.name,
+                        year: *year,
+                        report_type,
+                        companies: group.companies,
+                        documents: s.documents,
+                        reports: s.reports,
+                        pages: s.pages,
+                        size_kb: s.size_kb,
+                        average_pages: format!("{:.1}", s.get_average_pages()),
+                        average_size_kb: format!("{:.0}", s.get_average_size_kb()),
+                    })
+                    .map_err(Error::csv(path))?;
+            }
+        }
+    }
+    writer.flush().map_err(Error::io(path))
+}
@@ -0,0 +1,109 @@
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+pub fn sha256_file(file_path: &Path) -> io::Result<String> {
+    let mut file = File::open(file_path)?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+pub struct DocumentStore {
+    root: PathBuf,
+}
+
+impl DocumentStore {
+    pub fn new(root: &Path) -> DocumentStore {
+        DocumentStore {
+            root: root.to_path_buf(),
+        }
+    }
+
+    pub fn get_path(&self, sha256: &str) -> PathBuf {
+        self.get_related_path(sha256, "pdf")
+    }
+
+    /// Path for data derived from a stored document, e.g. its extracted text
+    pub fn get_related_path(&self, sha256: &str, extension: &str) -> PathBuf {
+        self.root
+            .join(&sha256[..2])
+            .join(format!("{}.{}", sha256, extension))
+    }
+
+    pub fn contains(&self, sha256: &str) -> bool {
+        self.get_path(sha256).exists()
+    }
+
+    /// Adds the file to the store and replaces it with a link to the stored copy.
+    /// Returns the hash of the file and whether its content was new to the store.
+    /// Several files with the same content can be inserted at the same time.
+    pub fn insert(&self, file_path: &Path) -> io::Result<(String, bool)> {
+        let sha256 = sha256_file(file_path)?;
+        let store_path = self.get_path(&sha256);
+        fs::create_dir_all(store_path.parent().unwrap())?;
+        // Linking fails if the content is already stored, also when a concurrent insert of the
+        // same content got there first
+        match fs::hard_link(file_path, &store_path) {
+            Ok(()) => Ok((sha256, true)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                if !is_same_file(file_path, &store_path)? {
+                    replace_with_link(&store_path, file_path)?;
+                }
+                Ok((sha256, false))
+            }
+            // Hardlinks fail across file systems, store a copy there
+            Err(_) => {
+                let temporary_path = get_temporary_path(&store_path);
+                fs::copy(file_path, &temporary_path)?;
+                match fs::rename(&temporary_path, &store_path) {
+                    Ok(()) => Ok((sha256, true)),
+                    // Stored in the meantime, with the same content
+                    Err(_) if store_path.exists() => {
+                        fs::remove_file(&temporary_path)?;
+                        Ok((sha256, false))
+                    }
+                    Err(e) => Err(e),
+                }
+            }
+        }
+    }
+}
+
+// A unique name in the same directory, so that it can be renamed to the path atomically
+fn get_temporary_path(path: &Path) -> PathBuf {
+    let mut temporary_path = path.as_os_str().to_owned();
+    temporary_path.push(format!(".{}.tmp", rand::random::<u32>()));
+    PathBuf::from(temporary_path)
+}
+
+// Replaces the file in one step, it never disappears for concurrent readers
+fn replace_with_link(source: &Path, destination: &Path) -> io::Result<()> {
+    let temporary_path = get_temporary_path(destination);
+    link_or_copy(source, &temporary_path)?;
+    fs::rename(&temporary_path, destination)
+}
+
+pub fn link_or_copy(source: &Path, destination: &Path) -> io::Result<()> {
+    // Hardlinks fail across file systems, fall back to a plain copy there
+    if fs::hard_link(source, destination).is_err() {
+        fs::copy(source, destination)?;
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn is_same_file(a: &Path, b: &Path) -> io::Result<bool> {
+    use std::os::unix::fs::MetadataExt;
+    let a = fs::metadata(a)?;
+    let b = fs::metadata(b)?;
+    Ok(a.dev() == b.dev() && a.ino() == b.ino())
+}
+
+#[cfg(not(unix))]
+fn is_same_file(_a: &Path, _b: &Path) -> io::Result<bool> {
+    Ok(false)
+}
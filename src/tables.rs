@@ -0,0 +1,495 @@
+use pdf_extract::{MediaBox, OutputDev, OutputError, Transform};
+use serde_derive::Serialize;
+use std::fs;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+
+use crate::extraction::ExtractionError;
+
+// Distances are relative to the font size of the text
+const WORD_GAP: f64 = 0.25;
+const COLUMN_GAP: f64 = 1.0;
+const SAME_LINE: f64 = 0.4;
+const MAX_ROW_DISTANCE: f64 = 2.5;
+const MIN_ROWS: usize = 3;
+
+struct Glyph {
+    x: f64,
+    y: f64,
+    width: f64,
+    size: f64,
+    text: String,
+}
+
+#[derive(Clone)]
+struct Word {
+    x0: f64,
+    x1: f64,
+    y: f64,
+    size: f64,
+    text: String,
+}
+
+impl Word {
+    fn from_glyph(glyph: &Glyph) -> Word {
+        Word {
+            x0: glyph.x,
+            x1: glyph.x + glyph.width,
+            y: glyph.y,
+            size: glyph.size,
+            text: glyph.text.clone(),
+        }
+    }
+
+    fn continues_with(&self, glyph: &Glyph) -> bool {
+        (glyph.y - self.y).abs() <= self.size * SAME_LINE
+            && glyph.x >= self.x1 - self.size * WORD_GAP
+            && glyph.x <= self.x1 + self.size * WORD_GAP
+    }
+
+    fn push(&mut self, glyph: &Glyph) {
+        self.x1 = glyph.x + glyph.width;
+        self.size = self.size.max(glyph.size);
+        self.text.push_str(&glyph.text);
+    }
+
+    fn is_numeric(&self) -> bool {
+        let digits = self.text.chars().filter(|c| c.is_ascii_digit()).count();
+        let letters = self.text.chars().filter(|c| c.is_alphabetic()).count();
+        digits > 0 && digits >= letters
+    }
+}
+
+struct Line {
+    y: f64,
+    size: f64,
+    cells: Vec<Word>,
+}
+
+impl Line {
+    fn is_tabular(&self) -> bool {
+        self.cells.len() >= 2 && self.cells.iter().any(|c| c.is_numeric())
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct Table {
+    pub page: u32,
+    /// Left, bottom, right and top edge in PDF points, measured from the bottom left corner
+    pub bbox: [f64; 4],
+    pub rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    pub fn get_columns(&self) -> usize {
+        self.rows.iter().map(|r| r.len()).max().unwrap_or(0)
+    }
+}
+
+// Collects every character of the document with its position
+#[derive(Default)]
+struct PositionedOutput {
+    pages: Vec<(u32, Vec<Glyph>)>,
+}
+
+impl OutputDev for PositionedOutput {
+    fn begin_page(
+        &mut self,
+        page_num: u32,
+        _media_box: &MediaBox,
+        _art_box: Option<(f64, f64, f64, f64)>,
+    ) -> Result<(), OutputError> {
+        self.pages.push((page_num, Vec::new()));
+        Ok(())
+    }
+
+    fn end_page(&mut self) -> Result<(), OutputError> {
+        Ok(())
+    }
+
+    fn output_character(
+        &mut self,
+        trm: &Transform,
+        width: f64,
+        _spacing: f64,
+        font_size: f64,
+        char: &str,
+    ) -> Result<(), OutputError> {
+        let size = font_size * (trm.m11 * trm.m22 - trm.m12 * trm.m21).abs().sqrt();
+        if let Some((_, glyphs)) = self.pages.last_mut() {
+            glyphs.push(Glyph {
+                x: trm.m31,
+                y: trm.m32,
+                width: width * size,
+                size,
+                text: char.to_owned(),
+            });
+        }
+        Ok(())
+    }
+
+    fn begin_word(&mut self) -> Result<(), OutputError> {
+        Ok(())
+    }
+
+    fn end_word(&mut self) -> Result<(), OutputError> {
+        Ok(())
+    }
+
+    fn end_line(&mut self) -> Result<(), OutputError> {
+        Ok(())
+    }
+}
+
+fn get_words(glyphs: &[Glyph]) -> Vec<Word> {
+    let mut words: Vec<Word> = Vec::new();
+    let mut current: Option<Word> = None;
+    for glyph in glyphs {
+        if glyph.text.trim().is_empty() {
+            words.extend(current.take());
+            continue;
+        }
+        match current.as_mut() {
+            Some(word) if word.continues_with(glyph) => word.push(glyph),
+            _ => {
+                words.extend(current.take());
+                current = Some(Word::from_glyph(glyph));
+            }
+        }
+    }
+    words.extend(current);
+    words
+}
+
+// Groups the words into lines from the top of the page down and joins the words of a line that
+// are close to each other into cells
+fn get_lines(mut words: Vec<Word>) -> Vec<Line> {
+    words.sort_by(|a, b| b.y.total_cmp(&a.y));
+    let mut lines: Vec<(f64, f64, Vec<Word>)> = Vec::new();
+    for word in words {
+        match lines.last_mut() {
+            Some((y, size, line_words))
+                if (*y - word.y).abs() <= size.max(word.size) * SAME_LINE =>
+            {
+                *size = size.max(word.size);
+                line_words.push(word);
+            }
+            _ => lines.push((word.y, word.size, vec![word])),
+        }
+    }
+    lines
+        .into_iter()
+        .map(|(y, size, mut line_words)| {
+            line_words.sort_by(|a, b| a.x0.total_cmp(&b.x0));
+            let mut cells: Vec<Word> = Vec::new();
+            for word in line_words {
+                match cells.last_mut() {
+                    Some(cell) if word.x0 - cell.x1 < cell.size.max(word.size) * COLUMN_GAP => {
+                        cell.text.push(' ');
+                        cell.text.push_str(&word.text);
+                        cell.x1 = cell.x1.max(word.x1);
+                    }
+                    _ => cells.push(word),
+                }
+            }
+            Line { y, size, cells }
+        })
+        .collect()
+}
+
+// Consecutive tabular lines form a table, single lines in between (e.g. subtitles) are kept
+fn get_blocks(lines: &[Line]) -> Vec<&[Line]> {
+    let mut blocks = Vec::new();
+    let mut start: Option<usize> = None;
+    let mut last_tabular = 0;
+    for (index, line) in lines.iter().enumerate() {
+        let is_close = index > 0 && lines[index - 1].y - line.y <= line.size * MAX_ROW_DISTANCE;
+        if let Some(block_start) = start {
+            let gap_lines = index - last_tabular - 1;
+            if !is_close || (!line.is_tabular() && gap_lines >= 1) {
+                blocks.push(&lines[block_start..=last_tabular]);
+                start = None;
+            }
+        }
+        if line.is_tabular() {
+            if start.is_none() {
+                // A header row directly above the numbers, e.g. with the years
+                let has_header = index > 0 && is_close && lines[index - 1].cells.len() >= 2;
+                start = Some(if has_header { index - 1 } else { index });
+            }
+            last_tabular = index;
+        }
+    }
+    if let Some(block_start) = start {
+        blocks.push(&lines[block_start..=last_tabular]);
+    }
+    blocks
+        .into_iter()
+        .filter(|b| b.iter().filter(|l| l.is_tabular()).count() >= MIN_ROWS)
+        .collect()
+}
+
+// The columns are the merged horizontal extents of the cells of all tabular rows
+fn get_columns(block: &[Line]) -> Vec<(f64, f64)> {
+    let mut extents: Vec<(f64, f64)> = block
+        .iter()
+        .filter(|l| l.is_tabular())
+        .flat_map(|l| l.cells.iter().map(|c| (c.x0, c.x1)))
+        .collect();
+    extents.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let mut columns: Vec<(f64, f64)> = Vec::new();
+    for (x0, x1) in extents {
+        match columns.last_mut() {
+            Some(column) if x0 <= column.1 => column.1 = column.1.max(x1),
+            _ => columns.push((x0, x1)),
+        }
+    }
+    columns
+}
+
+fn get_column_index(columns: &[(f64, f64)], cell: &Word) -> usize {
+    let center = (cell.x0 + cell.x1) / 2.0;
+    let distance = |column: &(f64, f64)| {
+        if center < column.0 {
+            column.0 - center
+        } else if center > column.1 {
+            center - column.1
+        } else {
+            0.0
+        }
+    };
+    (0..columns.len())
+        .min_by(|a, b| distance(&columns[*a]).total_cmp(&distance(&columns[*b])))
+        .unwrap_or(0)
+}
+
+fn create_table(page: u32, block: &[Line]) -> Table {
+    let columns = get_columns(block);
+    let mut rows = Vec::new();
+    let mut bbox = [f64::MAX, f64::MAX, f64::MIN, f64::MIN];
+    for line in block {
+        let mut row = vec![String::new(); columns.len()];
+        for cell in &line.cells {
+            let column = &mut row[get_column_index(&columns, cell)];
+            if !column.is_empty() {
+                column.push(' ');
+            }
+            column.push_str(&cell.text);
+            bbox[0] = bbox[0].min(cell.x0);
+            bbox[2] = bbox[2].max(cell.x1);
+        }
+        bbox[1] = bbox[1].min(line.y - line.size * 0.25);
+        bbox[3] = bbox[3].max(line.y + line.size);
+        rows.push(row);
+    }
+    Table {
+        page,
+        bbox: bbox.map(|v| (v * 10.0).round() / 10.0),
+        rows,
+    }
+}
+
+fn find_tables(file_path: &Path) -> Result<Vec<Table>, ExtractionError> {
+    let doc = pdf_extract_lopdf::Document::load(file_path)?;
+    let mut output = PositionedOutput::default();
+    pdf_extract::output_doc(&doc, &mut output).map_err(|e| format!("{:?}", e))?;
+    let mut tables = Vec::new();
+    for (page, glyphs) in &output.pages {
+        let lines = get_lines(get_words(glyphs));
+        for block in get_blocks(&lines) {
+            tables.push(create_table(*page, block));
+        }
+    }
+    Ok(tables)
+}
+
+/// Detects tables from the positions of the text on every page
+pub fn extract_tables(file_path: &Path) -> Result<Vec<Table>, ExtractionError> {
+    match catch_unwind(AssertUnwindSafe(|| find_tables(file_path))) {
+        Ok(tables) => tables,
+        Err(_) => Err("pdf-extract panicked".into()),
+    }
+}
+
+pub fn get_tables_directory(file_path: &Path) -> PathBuf {
+    let stem = file_path.file_stem().unwrap_or_default().to_string_lossy();
+    file_path.with_file_name(format!("{}-tables", stem))
+}
+
+#[derive(Serialize)]
+struct TableIndexEntry<'a> {
+    file: &'a str,
+    page: u32,
+    left: f64,
+    bottom: f64,
+    right: f64,
+    top: f64,
+    rows: usize,
+    columns: usize,
+}
+
+/// Writes every table as a CSV file and a tables.csv listing the page and position of each
+pub fn write_tables(directory: &Path, tables: &[Table]) -> Result<(), ExtractionError> {
+    if directory.exists() {
+        fs::remove_dir_all(directory)?;
+    }
+    fs::create_dir_all(directory)?;
+    let mut index = csv::WriterBuilder::new()
+        .delimiter(b';')
+        .from_path(directory.join("tables.csv"))?;
+    let mut page_tables = 0;
+    for (i, table) in tables.iter().enumerate() {
+        page_tables = match i {
+            0 => 1,
+            _ if tables[i - 1].page == table.page => page_tables + 1,
+            _ => 1,
+        };
+        let file = format!("page-{}-table-{}.csv", table.page, page_tables);
+        let mut writer = csv::WriterBuilder::new()
+            .delimiter(b';')
+            .flexible(true)
+            .from_path(directory.join(&file))?;
+        for row in &table.rows {
+            writer.write_record(row)?;
+        }
+        writer.flush()?;
+        index.serialize(TableIndexEntry {
+            file: &file,
+            page: table.page,
+            left: table.bbox[0],
+            bottom: table.bbox[1],
+            right: table.bbox[2],
+            top: table.bbox[3],
+            rows: table.rows.len(),
+            columns: table.get_columns(),
+        })?;
+    }
+    index.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: f64 = 10.0;
+    const CHAR_WIDTH: f64 = 5.0;
+
+    // Lays out the text of each cell character by character, starting at its x position
+    fn create_glyphs(y: f64, cells: &[(f64, &str)]) -> Vec<Glyph> {
+        let mut glyphs = Vec::new();
+        for (x, text) in cells {
+            for (i, c) in text.chars().enumerate() {
+                glyphs.push(Glyph {
+                    x: x + i as f64 * CHAR_WIDTH,
+                    y,
+                    width: CHAR_WIDTH,
+                    size: SIZE,
+                    text: c.to_string(),
+                });
+            }
+        }
+        glyphs
+    }
+
+    fn create_lines(rows: &[(f64, &[(f64, &str)])]) -> Vec<Line> {
+        let glyphs: Vec<Glyph> = rows
+            .iter()
+            .flat_map(|(y, cells)| create_glyphs(*y, cells))
+            .collect();
+        get_lines(get_words(&glyphs))
+    }
+
+    fn get_texts(line: &Line) -> Vec<&str> {
+        line.cells.iter().map(|c| c.text.as_str()).collect()
+    }
+
+    #[test]
+    fn adjacent_glyphs_are_joined_into_words() {
+        let mut glyphs = create_glyphs(700.0, &[(0.0, "Net revenues"), (100.0, "1'234")]);
+        glyphs.extend(create_glyphs(680.0, &[(160.0, "5")]));
+        let words: Vec<String> = get_words(&glyphs).into_iter().map(|w| w.text).collect();
+        assert_eq!(words, vec!["Net", "revenues", "1'234", "5"]);
+    }
+
+    #[test]
+    fn close_words_form_one_cell_and_lines_go_down_the_page() {
+        let lines = create_lines(&[
+            (680.0, &[(0.0, "Net profit"), (100.0, "12.5")]),
+            (
+                700.0,
+                &[(0.0, "Net revenues"), (100.0, "1'234"), (150.0, "1'100")],
+            ),
+        ]);
+        assert_eq!(lines.len(), 2);
+        assert_eq!(get_texts(&lines[0]), vec!["Net revenues", "1'234", "1'100"]);
+        assert_eq!(get_texts(&lines[1]), vec!["Net profit", "12.5"]);
+        assert!(lines.iter().all(|l| l.is_tabular()));
+    }
+
+    #[test]
+    fn header_row_above_the_numbers_is_part_of_the_table() {
+        let lines = create_lines(&[
+            (760.0, &[(0.0, "Key figures")]),
+            (
+                712.0,
+                &[(0.0, "in CHF m"), (100.0, "Current"), (150.0, "Prior")],
+            ),
+            (
+                700.0,
+                &[(0.0, "Revenue"), (100.0, "1'234"), (150.0, "1'100")],
+            ),
+            (688.0, &[(0.0, "Profit"), (100.0, "123"), (150.0, "110")]),
+            (676.0, &[(0.0, "Equity"), (100.0, "456"), (150.0, "400")]),
+        ]);
+        let blocks = get_blocks(&lines);
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].len(), 4);
+        assert_eq!(
+            get_texts(&blocks[0][0]),
+            vec!["in CHF m", "Current", "Prior"]
+        );
+    }
+
+    #[test]
+    fn blocks_with_too_few_rows_are_no_tables() {
+        let revenue: &[(f64, &str)] = &[(0.0, "Revenue"), (100.0, "1'234")];
+        let profit: &[(f64, &str)] = &[(0.0, "Profit"), (100.0, "123")];
+        let equity: &[(f64, &str)] = &[(0.0, "Equity"), (100.0, "456")];
+        let lines = create_lines(&[(700.0, revenue), (688.0, profit), (676.0, equity)]);
+        assert_eq!(get_blocks(&lines).len(), 1);
+        // The last row is too far below the others to belong to the same table
+        let lines = create_lines(&[(700.0, revenue), (688.0, profit), (500.0, equity)]);
+        assert!(get_blocks(&lines).is_empty());
+    }
+
+    #[test]
+    fn cells_are_assigned_to_their_columns() {
+        let lines = create_lines(&[
+            (712.0, &[(100.0, "2023"), (150.0, "2022")]),
+            (
+                700.0,
+                &[(0.0, "Revenue"), (95.0, "12'345"), (150.0, "1'100")],
+            ),
+            // The value of the current year is missing
+            (688.0, &[(0.0, "Profit"), (155.0, "110")]),
+            (676.0, &[(0.0, "Equity"), (105.0, "456"), (150.0, "400")]),
+        ]);
+        let blocks = get_blocks(&lines);
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(get_columns(blocks[0]).len(), 3);
+        let table = create_table(3, blocks[0]);
+        assert_eq!(table.page, 3);
+        assert_eq!(table.get_columns(), 3);
+        assert_eq!(
+            table.rows,
+            vec![
+                vec!["", "2023", "2022"],
+                vec!["Revenue", "12'345", "1'100"],
+                vec!["Profit", "", "110"],
+                vec!["Equity", "456", "400"],
+            ]
+        );
+        assert_eq!(table.bbox, [0.0, 673.5, 175.0, 722.0]);
+    }
+}
@@ -0,0 +1,162 @@
+use serde::de::{Deserialize, Deserializer};
+use serde::ser::{Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Language {
+    EN,
+    DE,
+    FR,
+    IT,
+    Other(String),
+}
+
+impl Language {
+    pub const ALL: &'static [Language] = &[Language::EN, Language::DE, Language::FR, Language::IT];
+
+    pub fn from_code(code: &str) -> Language {
+        match code.to_ascii_uppercase().as_str() {
+            "EN" => Language::EN,
+            "DE" => Language::DE,
+            "FR" => Language::FR,
+            "IT" => Language::IT,
+            _ => Language::Other(code.to_owned()),
+        }
+    }
+
+    pub fn code(&self) -> &str {
+        match self {
+            Language::EN => "EN",
+            Language::DE => "DE",
+            Language::FR => "FR",
+            Language::IT => "IT",
+            Language::Other(code) => code,
+        }
+    }
+
+    pub fn is_known(&self) -> bool {
+        !matches!(self, Language::Other(_))
+    }
+
+    // Position of the language in the translation tables, unknown languages fall back to English
+    fn index(&self) -> usize {
+        match self {
+            Language::EN | Language::Other(_) => 0,
+            Language::DE => 1,
+            Language::FR => 2,
+            Language::IT => 3,
+        }
+    }
+
+    pub fn name(&self, language: &Language) -> &str {
+        let names = match self {
+            Language::EN => ["English", "Englisch", "Anglais", "Inglese"],
+            Language::DE => ["German", "Deutsch", "Allemand", "Tedesco"],
+            Language::FR => ["French", "Französisch", "Français", "Francese"],
+            Language::IT => ["Italian", "Italienisch", "Italien", "Italiano"],
+            Language::Other(code) => return code,
+        };
+        names[language.index()]
+    }
+
+    pub fn native_name(&self) -> &str {
+        self.name(self)
+    }
+}
+
+include!(concat!(env!("OUT_DIR"), "/report_type.rs"));
+
+impl ReportType {
+    pub fn is_known(&self) -> bool {
+        !matches!(self, ReportType::Other(_))
+    }
+
+    pub fn name(&self, language: &Language) -> &str {
+        match self.names() {
+            Some(names) => names[language.index()],
+            None => self.code(),
+        }
+    }
+}
+
+macro_rules! impl_code_traits {
+    ($type:ident) => {
+        impl fmt::Display for $type {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "{}", self.code())
+            }
+        }
+
+        // Used for the command line, where an unknown code is most likely a typo that would
+        // silently match nothing
+        impl FromStr for $type {
+            type Err = String;
+
+            fn from_str(code: &str) -> Result<Self, Self::Err> {
+                match $type::from_code(code) {
+                    $type::Other(_) => {
+                        let codes: Vec<&str> = $type::ALL.iter().map(|t| t.code()).collect();
+                        Err(format!(
+                            "Unknown code '{}', expected one of {}",
+                            code,
+                            codes.join(", ")
+                        ))
+                    }
+                    known => Ok(known),
+                }
+            }
+        }
+
+        impl Serialize for $type {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_str(self.code())
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $type {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let code = String::deserialize(deserializer)?;
+                Ok($type::from_code(&code))
+            }
+        }
+    };
+}
+
+impl_code_traits!(Language);
+impl_code_traits!(ReportType);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codes_are_case_insensitive() {
+        assert_eq!(ReportType::from_code("ar"), ReportType::AnnualReport);
+        assert_eq!(ReportType::from_code("10k"), ReportType::SecForm10K);
+        assert_eq!("Gri".parse(), Ok(ReportType::Gri));
+        assert_eq!(Language::from_code("de"), Language::DE);
+        assert_eq!("it".parse(), Ok(Language::IT));
+        for report_type in ReportType::ALL {
+            assert_eq!(&ReportType::from_code(report_type.code()), report_type);
+        }
+    }
+
+    #[test]
+    fn unknown_codes_are_kept_but_rejected_on_the_command_line() {
+        // Source files may contain codes that are not known yet, they are kept as they are
+        let report_type = ReportType::from_code("xy");
+        assert_eq!(report_type, ReportType::Other("xy".to_owned()));
+        assert!(!report_type.is_known());
+        assert_eq!(report_type.to_string(), "xy");
+        assert_eq!(report_type.name(&Language::DE), "xy");
+        assert_eq!(Language::from_code("RM"), Language::Other("RM".to_owned()));
+
+        let error = "XY".parse::<ReportType>().unwrap_err();
+        assert!(error.starts_with("Unknown code 'XY', expected one of AR, FR, SR"));
+        assert_eq!(
+            "RM".parse::<Language>(),
+            Err("Unknown code 'RM', expected one of EN, DE, FR, IT".to_owned())
+        );
+    }
+}
@@ -0,0 +1,105 @@
+use serde_derive::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use crate::pdf_metadata::{read_metadata, PdfMetadata};
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub enum Validation {
+    Pdf,
+    Html,
+    Corrupt(String),
+    Encrypted,
+    NoPages,
+    UnexpectedType(String),
+}
+
+impl Validation {
+    pub fn is_valid(&self) -> bool {
+        *self == Validation::Pdf
+    }
+}
+
+impl fmt::Display for Validation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Validation::Pdf => write!(f, "PDF"),
+            Validation::Html => write!(f, "HTML page instead of PDF"),
+            Validation::Corrupt(reason) => write!(f, "corrupt PDF: {}", reason),
+            Validation::Encrypted => write!(f, "encrypted PDF"),
+            Validation::NoPages => write!(f, "PDF without pages"),
+            Validation::UnexpectedType(mime_type) => write!(f, "unexpected type {}", mime_type),
+        }
+    }
+}
+
+pub struct FileCheck {
+    pub mime_type: String,
+    pub validation: Validation,
+    pub pages: u32,
+    pub metadata: Option<PdfMetadata>,
+}
+
+pub fn validate_file(file_path: &Path) -> FileCheck {
+    let mime_type = if has_pdf_header(file_path) {
+        "application/pdf".to_owned()
+    } else {
+        tree_magic_mini::from_filepath(file_path)
+            .unwrap_or("application/octet-stream")
+            .to_owned()
+    };
+    let (validation, pages, metadata) = match mime_type.as_str() {
+        "application/pdf" => validate_pdf(file_path),
+        "text/html" | "application/xhtml+xml" => (Validation::Html, 0, None),
+        m if m.starts_with("text/") && looks_like_html(file_path) => (Validation::Html, 0, None),
+        _ => (Validation::UnexpectedType(mime_type.clone()), 0, None),
+    };
+    FileCheck {
+        mime_type,
+        validation,
+        pages,
+        metadata,
+    }
+}
+
+// The shared mime database does not recognize every pdf, readers accept the header anywhere in the first 1024 bytes
+fn has_pdf_header(file_path: &Path) -> bool {
+    let mut header = Vec::with_capacity(1024);
+    match File::open(file_path).and_then(|f| f.take(1024).read_to_end(&mut header)) {
+        Ok(_) => header.windows(5).any(|w| w == b"%PDF-"),
+        Err(_) => false,
+    }
+}
+
+// Short html snippets without a doctype are only detected as plain text
+fn looks_like_html(file_path: &Path) -> bool {
+    match fs::read(file_path) {
+        Ok(content) => {
+            let content = String::from_utf8_lossy(&content).to_lowercase();
+            content.contains("<html") || content.contains("<body")
+        }
+        Err(_) => false,
+    }
+}
+
+fn validate_pdf(file_path: &Path) -> (Validation, u32, Option<PdfMetadata>) {
+    let mut doc = match lopdf::Document::load(file_path) {
+        Ok(doc) => doc,
+        Err(e) => return (Validation::Corrupt(e.to_string()), 0, None),
+    };
+    // Most reports only carry an owner password, they can be read with an empty user password
+    let encrypted = doc.is_encrypted();
+    if encrypted && doc.decrypt("").is_err() {
+        return (Validation::Encrypted, doc.get_pages().len() as u32, None);
+    }
+    let pages = doc.get_pages().len() as u32;
+    let metadata = Some(read_metadata(&doc, encrypted));
+    if pages == 0 {
+        (Validation::NoPages, 0, metadata)
+    } else {
+        (Validation::Pdf, pages, metadata)
+    }
+}
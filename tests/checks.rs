@@ -0,0 +1,188 @@
+use std::net::{Ipv6Addr, TcpListener};
+use std::time::Duration;
+
+use annual_report_database::checks::{self, CheckRecord, CheckResult};
+use annual_report_database::data_structures::CompanyMetadata;
+use annual_report_database::output::{self, OutputFormat};
+
+mod common;
+
+use common::StubResolver;
+
+fn create_metadata(url: &str) -> CompanyMetadata {
+    CompanyMetadata {
+        url: url.to_owned(),
+        ..CompanyMetadata::new("Example")
+    }
+}
+
+fn create_record(details: &str) -> CheckRecord {
+    CheckRecord::new(
+        &create_metadata("https://www.example.ch"),
+        "www.example.ch",
+        "ipv6",
+        CheckResult::Pass,
+        details.to_owned(),
+    )
+}
+
+fn write(format: OutputFormat, records: &[CheckRecord]) -> String {
+    let mut out = Vec::new();
+    output::write_records(&mut out, format, records).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn records_have_the_company_and_a_utc_timestamp() {
+    let record = create_record("2001:db8::1");
+    assert_eq!(record.company, "Example");
+    assert_eq!(record.domain, "www.example.ch");
+    assert_eq!(record.check, "ipv6");
+    assert_eq!(record.result, CheckResult::Pass);
+    assert_eq!(record.details, "2001:db8::1");
+    // e.g. 2024-01-01T12:00:00Z
+    assert_eq!(record.timestamp.len(), 20);
+    assert!(record.timestamp.ends_with('Z'));
+    assert_eq!(
+        record.to_string(),
+        "Example, www.example.ch, ipv6: pass (2001:db8::1)"
+    );
+    assert_eq!(
+        create_record("").to_string(),
+        "Example, www.example.ch, ipv6: pass"
+    );
+}
+
+#[test]
+fn csv_output_has_a_header_and_semicolons() {
+    let records = [create_record("a; b"), create_record("")];
+    let csv = write(OutputFormat::Csv, &records);
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[0], "company;domain;check;result;details;timestamp");
+    assert_eq!(
+        lines[1],
+        format!(
+            "Example;www.example.ch;ipv6;pass;\"a; b\";{}",
+            records[0].timestamp
+        )
+    );
+    assert_eq!(
+        lines[2],
+        format!("Example;www.example.ch;ipv6;pass;;{}", records[1].timestamp)
+    );
+}
+
+#[test]
+fn json_output_has_one_object_per_line() {
+    let records = [create_record("2001:db8::1"), create_record("")];
+    let json = write(OutputFormat::Json, &records);
+    let lines: Vec<&str> = json.lines().collect();
+    assert_eq!(lines.len(), 2);
+    let object: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+    assert_eq!(object["company"], "Example");
+    assert_eq!(object["domain"], "www.example.ch");
+    assert_eq!(object["check"], "ipv6");
+    assert_eq!(object["result"], "pass");
+    assert_eq!(object["details"], "2001:db8::1");
+    assert_eq!(object["timestamp"], records[0].timestamp.as_str());
+    assert_eq!(object.as_object().unwrap().len(), 6);
+}
+
+#[test]
+fn text_output_has_one_line_per_record() {
+    let records = [create_record("2001:db8::1"), create_record("")];
+    assert_eq!(
+        write(OutputFormat::Text, &records),
+        "Example, www.example.ch, ipv6: pass (2001:db8::1)\nExample, www.example.ch, ipv6: pass\n"
+    );
+}
+
+#[tokio::test]
+async fn ipv6_check_lists_the_addresses() {
+    let metadata = create_metadata("https://www.example.ch");
+    let resolver = StubResolver::default()
+        .aaaa("www.example.ch", "2001:db8::1".parse::<Ipv6Addr>().unwrap())
+        .aaaa("www.example.ch", "2001:db8::2".parse::<Ipv6Addr>().unwrap());
+    let record = checks::check_ipv6(&resolver, &metadata).await;
+    assert_eq!(record.domain, "www.example.ch");
+    assert_eq!(record.check, "ipv6");
+    assert_eq!(record.result, CheckResult::Pass);
+    assert_eq!(record.details, "2001:db8::1 2001:db8::2");
+
+    let record = checks::check_ipv6(&StubResolver::default(), &metadata).await;
+    assert_eq!(record.result, CheckResult::Fail);
+    assert_eq!(record.details, "no AAAA record");
+
+    let resolver = StubResolver::default().failing("www.example.ch");
+    let record = checks::check_ipv6(&resolver, &metadata).await;
+    assert_eq!(record.result, CheckResult::Error);
+    assert_eq!(record.details, "lookup timed out");
+}
+
+#[tokio::test]
+async fn spf_check_uses_the_domain_without_www() {
+    let metadata = create_metadata("https://www.example.ch");
+    let resolver = StubResolver::default()
+        .txt("example.ch", "google-site-verification=abc")
+        .txt("example.ch", "v=spf1 mx -all");
+    let record = checks::check_spf(&resolver, &metadata).await;
+    assert_eq!(record.domain, "example.ch");
+    assert_eq!(record.check, "spf");
+    assert_eq!(record.result, CheckResult::Pass);
+    assert_eq!(record.details, "v=spf1 mx -all");
+
+    let cases = [
+        (
+            StubResolver::default(),
+            CheckResult::Fail,
+            "no SPF record".to_owned(),
+        ),
+        (
+            StubResolver::default().txt("example.ch", "v=spf1 ip4:300.0.0.1 -all"),
+            CheckResult::Fail,
+            "invalid record: v=spf1 ip4:300.0.0.1 -all".to_owned(),
+        ),
+        (
+            StubResolver::default()
+                .txt("example.ch", "v=spf1 mx -all")
+                .txt("example.ch", "v=spf1 -all"),
+            CheckResult::Fail,
+            "multiple records: v=spf1 mx -all | v=spf1 -all".to_owned(),
+        ),
+        (
+            StubResolver::default().failing("example.ch"),
+            CheckResult::Error,
+            "lookup timed out".to_owned(),
+        ),
+    ];
+    for (resolver, result, details) in cases.iter() {
+        let record = checks::check_spf(resolver, &metadata).await;
+        assert_eq!(record.result, *result, "{}", details);
+        assert_eq!(&record.details, details);
+    }
+}
+
+#[tokio::test]
+async fn tls_check_fails_if_no_connection_is_possible() {
+    let client = checks::create_tls13_client(Duration::from_secs(5)).unwrap();
+    let record = checks::check_tls(&client, &create_metadata("https://127.0.0.1:1")).await;
+    assert_eq!(record.domain, "127.0.0.1:1");
+    assert_eq!(record.check, "tls");
+    assert_eq!(record.result, CheckResult::Fail);
+    assert!(!record.details.is_empty());
+
+    let record = checks::check_tls(&client, &create_metadata("not a url")).await;
+    assert_eq!(record.result, CheckResult::Error);
+}
+
+#[tokio::test]
+async fn tls_check_reports_an_error_if_the_website_does_not_answer() {
+    // The connection is accepted by the operating system, but the handshake never starts
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("https://{}", listener.local_addr().unwrap());
+    let client = checks::create_tls13_client(Duration::from_millis(200)).unwrap();
+    let record = checks::check_tls(&client, &create_metadata(&url)).await;
+    assert_eq!(record.result, CheckResult::Error);
+    assert!(record.details.contains("timed out"), "{}", record.details);
+}
@@ -0,0 +1,117 @@
+// Not every test crate uses every helper
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use async_trait::async_trait;
+use lopdf::{dictionary, Document, Object, Stream};
+use viaspf::lookup::{Lookup, LookupError, LookupResult, Name};
+
+// Answers from fixed records instead of the DNS, names that are not known do not exist
+#[derive(Default)]
+pub struct StubResolver {
+    pub txt: HashMap<String, Vec<String>>,
+    pub mx: HashMap<String, Vec<String>>,
+    pub aaaa: HashMap<String, Vec<Ipv6Addr>>,
+    pub failing: Vec<String>,
+}
+
+impl StubResolver {
+    pub fn txt(mut self, name: &str, record: &str) -> Self {
+        self.txt
+            .entry(format!("{}.", name))
+            .or_default()
+            .push(record.to_owned());
+        self
+    }
+
+    pub fn mx(mut self, name: &str, exchange: &str) -> Self {
+        self.mx
+            .entry(format!("{}.", name))
+            .or_default()
+            .push(exchange.to_owned());
+        self
+    }
+
+    pub fn aaaa(mut self, name: &str, address: Ipv6Addr) -> Self {
+        self.aaaa
+            .entry(format!("{}.", name))
+            .or_default()
+            .push(address);
+        self
+    }
+
+    pub fn failing(mut self, name: &str) -> Self {
+        self.failing.push(format!("{}.", name));
+        self
+    }
+
+    fn get<'a, T>(
+        &self,
+        records: &'a HashMap<String, Vec<T>>,
+        name: &Name,
+    ) -> LookupResult<&'a Vec<T>> {
+        if self.failing.iter().any(|n| n == name.as_str()) {
+            return Err(LookupError::Timeout);
+        }
+        records.get(name.as_str()).ok_or(LookupError::NoRecords)
+    }
+}
+
+#[async_trait]
+impl Lookup for StubResolver {
+    async fn lookup_a<'lookup, 'a>(&'lookup self, _: &'a Name) -> LookupResult<Vec<Ipv4Addr>> {
+        Err(LookupError::NoRecords)
+    }
+
+    async fn lookup_aaaa<'lookup, 'a>(
+        &'lookup self,
+        name: &'a Name,
+    ) -> LookupResult<Vec<Ipv6Addr>> {
+        Ok(self.get(&self.aaaa, name)?.clone())
+    }
+
+    async fn lookup_mx<'lookup, 'a>(&'lookup self, name: &'a Name) -> LookupResult<Vec<Name>> {
+        Ok(self
+            .get(&self.mx, name)?
+            .iter()
+            .map(|exchange| Name::new(exchange).unwrap())
+            .collect())
+    }
+
+    async fn lookup_txt<'lookup, 'a>(&'lookup self, name: &'a Name) -> LookupResult<Vec<String>> {
+        Ok(self.get(&self.txt, name)?.clone())
+    }
+
+    async fn lookup_ptr<'lookup>(&'lookup self, _: IpAddr) -> LookupResult<Vec<Name>> {
+        Err(LookupError::NoRecords)
+    }
+}
+
+// A valid pdf with one empty page
+pub fn create_pdf() -> Vec<u8> {
+    let mut doc = Document::with_version("1.5");
+    let pages_id = doc.new_object_id();
+    let content_id = doc.add_object(Stream::new(dictionary! {}, Vec::new()));
+    let page_id = doc.add_object(dictionary! {
+        "Type" => "Page",
+        "Parent" => pages_id,
+        "Contents" => content_id,
+        "MediaBox" => vec![0.into(), 0.into(), 595.into(), 842.into()],
+    });
+    let pages = dictionary! {
+        "Type" => "Pages",
+        "Kids" => vec![page_id.into()],
+        "Count" => 1,
+    };
+    doc.objects.insert(pages_id, Object::Dictionary(pages));
+    let catalog_id = doc.add_object(dictionary! {
+        "Type" => "Catalog",
+        "Pages" => pages_id,
+    });
+    doc.trailer.set("Root", catalog_id);
+    let mut buffer = Vec::new();
+    doc.save_to(&mut buffer).unwrap();
+    buffer
+}
@@ -0,0 +1,188 @@
+use viaspf_record::Qualifier;
+
+use annual_report_database::email_security::{self, Grade};
+
+mod common;
+
+use common::StubResolver;
+
+fn create_secure_domain() -> StubResolver {
+    StubResolver::default()
+        .txt("example.ch", "v=spf1 mx include:_spf.mail.ch -all")
+        .txt("_spf.mail.ch", "v=spf1 ip4:192.0.2.0/24 ~all")
+        .txt(
+            "_dmarc.example.ch",
+            "v=DMARC1; p=reject; rua=mailto:dmarc@example.ch",
+        )
+        .txt("_mta-sts.example.ch", "v=STSv1; id=20240101")
+        .txt(
+            "_smtp._tls.example.ch",
+            "v=TLSRPTv1; rua=mailto:tls@example.ch",
+        )
+        .txt(
+            "selector1._domainkey.example.ch",
+            "v=DKIM1; k=rsa; p=MIGfMA0GCSqGSIb3",
+        )
+        .mx("example.ch", "mx1.mail.ch")
+}
+
+#[tokio::test]
+async fn secure_domain_gets_an_a() {
+    let audit =
+        email_security::audit_domain(&create_secure_domain(), "Example", "example.ch").await;
+    assert_eq!(audit.grade, Some(Grade::A));
+    assert_eq!(audit.spf, Some(Grade::A));
+    assert_eq!(audit.spf_details, "-all, 2 DNS lookups");
+    assert_eq!(audit.mx_details, "mx1.mail.ch");
+    assert_eq!(audit.dkim_details, "selectors selector1");
+}
+
+#[tokio::test]
+async fn missing_transport_security_lowers_an_a_to_b() {
+    let mut resolver = create_secure_domain();
+    resolver.txt.remove("_mta-sts.example.ch.");
+    let audit = email_security::audit_domain(&resolver, "Example", "example.ch").await;
+    assert_eq!(audit.mta_sts, Some(Grade::F));
+    assert_eq!(audit.grade, Some(Grade::B));
+}
+
+#[tokio::test]
+async fn weak_all_qualifiers_are_graded_down() {
+    let cases = [
+        ("v=spf1 mx -all", Grade::A),
+        ("v=spf1 mx ~all", Grade::B),
+        ("v=spf1 mx ?all", Grade::D),
+        ("v=spf1 mx", Grade::D),
+        ("v=spf1 mx +all", Grade::F),
+    ];
+    for (record, grade) in cases.iter() {
+        let resolver = StubResolver::default().txt("example.ch", record);
+        let finding = email_security::audit_spf(&resolver, "example.ch").await;
+        assert_eq!(finding.grade, Some(*grade), "{}", record);
+    }
+}
+
+#[tokio::test]
+async fn redirect_decides_the_policy() {
+    let resolver = StubResolver::default()
+        .txt("example.ch", "v=spf1 redirect=_spf.example.ch")
+        .txt("_spf.example.ch", "v=spf1 include:_spf.mail.ch -all")
+        .txt("_spf.mail.ch", "v=spf1 +all");
+    let evaluation =
+        email_security::evaluate_spf(&resolver, "example.ch", "v=spf1 redirect=_spf.example.ch")
+            .await;
+    assert_eq!(evaluation.all, Some(Qualifier::Fail));
+    assert_eq!(evaluation.lookups, 2);
+    assert_eq!(evaluation.includes, vec!["_spf.example.ch", "_spf.mail.ch"]);
+    assert!(evaluation.problems.is_empty());
+}
+
+#[tokio::test]
+async fn lookups_of_includes_count_towards_the_limit() {
+    let record = "v=spf1 a mx include:a.example.ch include:b.example.ch -all";
+    let resolver = StubResolver::default()
+        .txt("example.ch", record)
+        .txt(
+            "a.example.ch",
+            "v=spf1 a mx exists:%{i}.example.ch ptr -all",
+        )
+        .txt("b.example.ch", "v=spf1 a mx include:c.example.ch -all")
+        .txt("c.example.ch", "v=spf1 a mx -all");
+    let evaluation = email_security::evaluate_spf(&resolver, "example.ch", record).await;
+    // The evaluation stops before c.example.ch, as soon as the limit is exceeded
+    assert_eq!(evaluation.lookups, 11);
+    assert_eq!(evaluation.problems, vec!["more than 10 DNS lookups"]);
+    let finding = email_security::audit_spf(&resolver, "example.ch").await;
+    assert_eq!(finding.grade, Some(Grade::F));
+}
+
+#[tokio::test]
+async fn broken_include_chains_fail() {
+    let record = "v=spf1 include:a.example.ch include:missing.example.ch -all";
+    let resolver = StubResolver::default()
+        .txt("example.ch", record)
+        .txt("a.example.ch", "v=spf1 include:b.example.ch -all")
+        .txt("b.example.ch", "v=spf1 include:a.example.ch -all");
+    let evaluation = email_security::evaluate_spf(&resolver, "example.ch", record).await;
+    assert_eq!(
+        evaluation.problems,
+        vec![
+            "missing.example.ch: no SPF record",
+            "loop: example.ch -> a.example.ch -> b.example.ch -> a.example.ch",
+        ]
+    );
+    let finding = email_security::audit_spf(&resolver, "example.ch").await;
+    assert_eq!(finding.grade, Some(Grade::F));
+}
+
+#[tokio::test]
+async fn dmarc_policies_are_graded() {
+    let cases = [
+        ("v=DMARC1; p=reject; rua=mailto:d@example.ch", Grade::A),
+        (
+            "v=DMARC1; p=reject; sp=none; rua=mailto:d@example.ch",
+            Grade::B,
+        ),
+        (
+            "v=DMARC1; p=quarantine; pct=50; rua=mailto:d@example.ch",
+            Grade::C,
+        ),
+        ("v=DMARC1; p=none; rua=mailto:d@example.ch", Grade::D),
+        ("v=DMARC1; p=none", Grade::D),
+        ("v=DMARC1; p=everything", Grade::F),
+    ];
+    for (record, grade) in cases.iter() {
+        let resolver = StubResolver::default().txt("_dmarc.example.ch", record);
+        let finding = email_security::audit_dmarc(&resolver, "example.ch").await;
+        assert_eq!(finding.grade, Some(*grade), "{}", record);
+    }
+    let finding = email_security::audit_dmarc(&StubResolver::default(), "example.ch").await;
+    assert_eq!(finding.grade, Some(Grade::F));
+    assert_eq!(finding.details, "no DMARC record");
+}
+
+#[tokio::test]
+async fn failed_lookups_are_not_graded() {
+    let resolver = create_secure_domain().failing("_dmarc.example.ch");
+    let audit = email_security::audit_domain(&resolver, "Example", "example.ch").await;
+    assert_eq!(audit.dmarc, None);
+    assert_eq!(audit.dmarc_details, "lookup timed out");
+    assert_eq!(audit.grade, None);
+}
+
+#[tokio::test]
+async fn missing_dkim_key_does_not_lower_the_grade() {
+    let mut resolver = create_secure_domain();
+    resolver.txt.remove("selector1._domainkey.example.ch.");
+    let audit = email_security::audit_domain(&resolver, "Example", "example.ch").await;
+    assert_eq!(audit.dkim, Some(Grade::D));
+    assert_eq!(audit.dkim_details, "no key for the common selectors");
+    assert_eq!(audit.grade, Some(Grade::A));
+}
+
+#[tokio::test]
+async fn domain_without_mail_servers_fails() {
+    let mut resolver = create_secure_domain();
+    resolver.mx.remove("example.ch.");
+    let audit = email_security::audit_domain(&resolver, "Example", "example.ch").await;
+    assert_eq!(audit.mx, Some(Grade::F));
+    assert_eq!(audit.grade, Some(Grade::F));
+}
+
+#[tokio::test]
+async fn void_lookups_count_towards_their_limit() {
+    let record = "v=spf1 a mx a:old.example.ch exists:%{i}.example.ch include:_spf.mail.ch -all";
+    let resolver = create_secure_domain().aaaa("example.ch", "2001:db8::1".parse().unwrap());
+    let evaluation = email_security::evaluate_spf(&resolver, "example.ch", record).await;
+    // Only old.example.ch does not exist, the exists mechanism depends on the sender
+    assert_eq!(evaluation.void_lookups, 1);
+    assert!(evaluation.problems.is_empty());
+
+    let record = "v=spf1 a:old.example.ch mx:old.example.ch a:older.example.ch -all";
+    let resolver = StubResolver::default().txt("example.ch", record);
+    let evaluation = email_security::evaluate_spf(&resolver, "example.ch", record).await;
+    assert_eq!(evaluation.void_lookups, 3);
+    assert_eq!(evaluation.problems, vec!["more than 2 void DNS lookups"]);
+    let finding = email_security::audit_spf(&resolver, "example.ch").await;
+    assert_eq!(finding.grade, Some(Grade::F));
+}
@@ -0,0 +1,83 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use structopt::StructOpt;
+
+use annual_report_database::data_structures::{Configuration, Language, Report, ReportType};
+use annual_report_database::extraction::{ExtractionJob, Extractor};
+use annual_report_database::store::DocumentStore;
+
+mod common;
+
+use common::create_pdf;
+
+fn create_directory(name: &str) -> PathBuf {
+    let directory =
+        std::env::temp_dir().join(format!("annual_report_database_extraction_{}", name));
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(directory.join("run")).unwrap();
+    directory
+}
+
+fn create_extractor(directory: &Path) -> Extractor {
+    let c = Configuration::from_iter(&[
+        "test",
+        "--download-directory",
+        directory.to_str().unwrap(),
+        "--extractor",
+        "lopdf",
+        "--no-ocr",
+    ]);
+    Extractor::new(&c, std::time::Duration::from_secs(10))
+}
+
+// Jobs for the same content linked for both languages, stored like the downloader does
+fn create_jobs(directory: &Path, content: &[u8]) -> Vec<ExtractionJob> {
+    let store = DocumentStore::new(&directory.join("store"));
+    [Language::DE, Language::EN]
+        .iter()
+        .map(|language| {
+            let file_path = directory.join("run").join(format!("AR-{}.pdf", language));
+            fs::write(&file_path, content).unwrap();
+            let (sha256, _) = store.insert(&file_path).unwrap();
+            ExtractionJob {
+                report: Report {
+                    company: "Test AG".to_owned(),
+                    language: language.clone(),
+                    report_type: ReportType::AnnualReport,
+                    year: 2020,
+                    link: format!("https://example.ch/AR-{}.pdf", language),
+                    alternative_links: Vec::new(),
+                },
+                sha256,
+                file_path,
+            }
+        })
+        .collect()
+}
+
+#[test]
+fn duplicates_are_extracted_once_and_taken_from_the_cache_later() {
+    let directory = create_directory("duplicates");
+    let extractor = create_extractor(&directory);
+
+    let manifest = extractor.extract_all(create_jobs(&directory, &create_pdf()), 2);
+    let cached: Vec<bool> = manifest.documents.iter().map(|d| d.cached).collect();
+    assert_eq!(cached, vec![false, true]);
+    assert_eq!(manifest.get_failures().count(), 0);
+    assert!(directory.join("run").join("AR-EN.txt").exists());
+
+    let manifest = extractor.extract_all(create_jobs(&directory, &create_pdf()), 2);
+    assert!(manifest.documents.iter().all(|d| d.cached));
+}
+
+#[test]
+fn failed_duplicates_are_not_cached() {
+    let directory = create_directory("failed");
+    let extractor = create_extractor(&directory);
+
+    let manifest = extractor.extract_all(create_jobs(&directory, b"not a pdf"), 2);
+    assert_eq!(manifest.documents.len(), 2);
+    assert_eq!(manifest.get_failures().count(), 2);
+    assert!(manifest.documents.iter().all(|d| !d.cached));
+}
@@ -0,0 +1,376 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use structopt::StructOpt;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+use annual_report_database::data_structures::{
+    Configuration, Language, Report, ReportType, RunManifest,
+};
+use annual_report_database::downloader::Downloader;
+use annual_report_database::validation::Validation;
+
+mod common;
+
+use common::create_pdf;
+
+// The value of a request header, e.g. "bytes=100-" for range
+fn get_header<'a>(request: &'a str, name: &str) -> Option<&'a str> {
+    request
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(key, _)| key.trim().eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.trim())
+}
+
+const ETAG: &str = "\"v1\"";
+
+const SLOW_RESPONSE: Duration = Duration::from_millis(500);
+
+// A minimal stand-in for a company website: one pdf that can also be downloaded in parts or
+// slowly, documents whose connection breaks or stalls in the middle, one disclaimer page,
+// everything else is missing
+async fn start_server() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    let pdf = create_pdf();
+    tokio::spawn(async move {
+        loop {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let pdf = pdf.clone();
+            tokio::spawn(async move {
+                let mut buffer = [0; 4096];
+                let n = socket.read(&mut buffer).await.unwrap();
+                let request = String::from_utf8_lossy(&buffer[..n]).to_string();
+                let path = request.split_whitespace().nth(1).unwrap_or("/");
+                let range_start = get_header(&request, "range")
+                    .and_then(|r| r.strip_prefix("bytes="))
+                    .and_then(|r| r.trim_end_matches('-').parse::<usize>().ok());
+                let mut headers = String::new();
+                let mut declared_length = None;
+                let mut stall = Duration::from_millis(0);
+                let (status, content_type, body) = match path {
+                    "/report.pdf" => ("200 OK", "application/pdf", pdf),
+                    "/slow.pdf" => {
+                        tokio::time::sleep(SLOW_RESPONSE).await;
+                        ("200 OK", "application/pdf", pdf)
+                    }
+                    "/resumable.pdf" => match range_start {
+                        Some(start) if get_header(&request, "if-range") == Some(ETAG) => {
+                            headers = format!(
+                                "Content-Range: bytes {}-{}/{}\r\n",
+                                start,
+                                pdf.len() - 1,
+                                pdf.len()
+                            );
+                            (
+                                "206 Partial Content",
+                                "application/pdf",
+                                pdf[start..].to_vec(),
+                            )
+                        }
+                        _ => {
+                            headers = format!("ETag: {}\r\n", ETAG);
+                            ("200 OK", "application/pdf", pdf)
+                        }
+                    },
+                    "/broken.pdf" => {
+                        // Announces more than it sends, with the same ETag as the resumable pdf
+                        headers = format!("ETag: {}\r\n", ETAG);
+                        declared_length = Some(200);
+                        // Give the client time to receive the body before the connection breaks
+                        stall = Duration::from_millis(100);
+                        ("200 OK", "application/pdf", vec![b'x'; 100])
+                    }
+                    "/stalled.pdf" => {
+                        // Sends half of the document and then nothing for a long time
+                        declared_length = Some(pdf.len());
+                        stall = Duration::from_secs(30);
+                        ("200 OK", "application/pdf", pdf[..pdf.len() / 2].to_vec())
+                    }
+                    "/disclaimer.pdf" => (
+                        "200 OK",
+                        "text/html",
+                        b"<html><body>Please accept the disclaimer</body></html>".to_vec(),
+                    ),
+                    _ => (
+                        "404 Not Found",
+                        "text/html",
+                        b"<html><body>Not found</body></html>".to_vec(),
+                    ),
+                };
+                let header = format!(
+                    "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n{}Connection: close\r\n\r\n",
+                    status,
+                    content_type,
+                    declared_length.unwrap_or(body.len()),
+                    headers
+                );
+                socket.write_all(header.as_bytes()).await.unwrap();
+                socket.write_all(&body).await.unwrap();
+                tokio::time::sleep(stall).await;
+            });
+        }
+    });
+    format!("http://{}", address)
+}
+
+fn create_downloader(name: &str) -> (Downloader, PathBuf) {
+    create_downloader_with(name, &[])
+}
+
+fn create_downloader_with(name: &str, args: &[&str]) -> (Downloader, PathBuf) {
+    let directory = std::env::temp_dir().join(format!("annual_report_database_{}", name));
+    let _ = std::fs::remove_dir_all(&directory);
+    (open_downloader(&directory, args), directory.join("run"))
+}
+
+// A downloader for a download directory that may already contain runs
+fn open_downloader(directory: &Path, args: &[&str]) -> Downloader {
+    let mut all_args = vec![
+        "test",
+        "--download-directory",
+        directory.to_str().unwrap(),
+        "--max-retries",
+        "0",
+        "--host-delay-ms",
+        "0",
+    ];
+    all_args.extend(args);
+    Downloader::new(&Configuration::from_iter(&all_args)).unwrap()
+}
+
+fn create_report(link: String, alternative_links: Vec<String>) -> Report {
+    Report {
+        company: "Test AG".to_owned(),
+        language: Language::EN,
+        report_type: ReportType::AnnualReport,
+        year: 2020,
+        link,
+        alternative_links,
+    }
+}
+
+#[tokio::test]
+async fn primary_link_is_used_when_valid() {
+    let server = start_server().await;
+    let (downloader, root_path) = create_downloader("primary");
+    let report = create_report(
+        format!("{}/report.pdf", server),
+        vec![format!("{}/missing.pdf", server)],
+    );
+
+    let download = downloader.download(&root_path, report).await.unwrap();
+    assert_eq!(download.validation, Validation::Pdf);
+    assert!(!download.is_from_fallback());
+}
+
+#[tokio::test]
+async fn fallback_is_used_when_primary_is_missing() {
+    let server = start_server().await;
+    let (downloader, root_path) = create_downloader("missing");
+    let report = create_report(
+        format!("{}/missing.pdf", server),
+        vec![format!("{}/report.pdf", server)],
+    );
+
+    let download = downloader.download(&root_path, report).await.unwrap();
+    assert_eq!(download.validation, Validation::Pdf);
+    assert!(download.is_from_fallback());
+    assert_eq!(download.source_link, format!("{}/report.pdf", server));
+}
+
+#[tokio::test]
+async fn fallback_is_used_when_primary_returns_html() {
+    let server = start_server().await;
+    let (downloader, root_path) = create_downloader("html");
+    let report = create_report(
+        format!("{}/disclaimer.pdf", server),
+        vec![
+            format!("{}/missing.pdf", server),
+            format!("{}/report.pdf", server),
+        ],
+    );
+
+    let download = downloader.download(&root_path, report).await.unwrap();
+    assert_eq!(download.validation, Validation::Pdf);
+    assert_eq!(download.source_link, format!("{}/report.pdf", server));
+}
+
+#[tokio::test]
+async fn invalid_primary_is_kept_when_all_fallbacks_fail() {
+    let server = start_server().await;
+    let (downloader, root_path) = create_downloader("failing");
+    let report = create_report(
+        format!("{}/disclaimer.pdf", server),
+        vec![format!("{}/missing.pdf", server)],
+    );
+
+    let download = downloader.download(&root_path, report).await.unwrap();
+    assert_eq!(download.validation, Validation::Html);
+    assert!(!download.is_from_fallback());
+}
+
+#[tokio::test]
+async fn download_fails_when_no_link_works() {
+    let server = start_server().await;
+    let (downloader, root_path) = create_downloader("none");
+    let report = create_report(
+        format!("{}/missing.pdf", server),
+        vec![format!("{}/also_missing.pdf", server)],
+    );
+
+    assert!(downloader.download(&root_path, report).await.is_err());
+}
+
+// Leaves a partial download, as if an earlier run was interrupted
+fn create_partial_download(root_path: &Path, report: &Report, content: &[u8], validator: &str) {
+    let file_path = report.get_file_path(root_path);
+    std::fs::create_dir_all(file_path.parent().unwrap()).unwrap();
+    let part_path = PathBuf::from(format!("{}.part", file_path.display()));
+    std::fs::write(&part_path, content).unwrap();
+    std::fs::write(format!("{}.validator", part_path.display()), validator).unwrap();
+}
+
+#[tokio::test]
+async fn partial_download_is_resumed() {
+    let server = start_server().await;
+    let (downloader, root_path) = create_downloader("resume");
+    let report = create_report(format!("{}/resumable.pdf", server), Vec::new());
+    create_partial_download(&root_path, &report, &create_pdf()[..100], ETAG);
+    let file_path = report.get_file_path(&root_path);
+
+    let download = downloader.download(&root_path, report).await.unwrap();
+    assert_eq!(download.validation, Validation::Pdf);
+    assert_eq!(std::fs::read(file_path).unwrap(), create_pdf());
+}
+
+#[tokio::test]
+async fn changed_document_is_downloaded_again() {
+    let server = start_server().await;
+    let (downloader, root_path) = create_downloader("changed");
+    let report = create_report(format!("{}/resumable.pdf", server), Vec::new());
+    create_partial_download(&root_path, &report, b"an older version", "\"v0\"");
+    let file_path = report.get_file_path(&root_path);
+
+    let download = downloader.download(&root_path, report).await.unwrap();
+    assert_eq!(download.validation, Validation::Pdf);
+    assert_eq!(std::fs::read(file_path).unwrap(), create_pdf());
+}
+
+#[tokio::test]
+async fn fallback_does_not_resume_the_primary_download() {
+    let server = start_server().await;
+    let (downloader, root_path) = create_downloader("broken");
+    let report = create_report(
+        format!("{}/broken.pdf", server),
+        vec![format!("{}/resumable.pdf", server)],
+    );
+    let file_path = report.get_file_path(&root_path);
+
+    let download = downloader.download(&root_path, report).await.unwrap();
+    assert_eq!(download.validation, Validation::Pdf);
+    assert_eq!(download.source_link, format!("{}/resumable.pdf", server));
+    assert_eq!(std::fs::read(file_path).unwrap(), create_pdf());
+}
+
+#[tokio::test]
+async fn other_hosts_progress_while_one_host_is_saturated() {
+    let server = start_server().await;
+    // The same server under a second host name
+    let other_server = server.replace("127.0.0.1", "localhost");
+    let (downloader, root_path) = create_downloader_with(
+        "hosts",
+        &[
+            "--max-concurrent-downloads",
+            "2",
+            "--max-downloads-per-host",
+            "1",
+        ],
+    );
+    let downloader = Arc::new(downloader);
+    let mut slow_downloads = Vec::new();
+    for year in 2016..2020 {
+        let mut report = create_report(format!("{}/slow.pdf", server), Vec::new());
+        report.year = year;
+        let downloader = downloader.clone();
+        let root_path = root_path.clone();
+        slow_downloads.push(tokio::spawn(async move {
+            downloader.download(&root_path, report).await.unwrap()
+        }));
+    }
+    // Let the slow downloads queue up for their host first
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let start = Instant::now();
+    let report = create_report(format!("{}/report.pdf", other_server), Vec::new());
+    let download = downloader.download(&root_path, report).await.unwrap();
+    assert_eq!(download.validation, Validation::Pdf);
+    assert!(start.elapsed() < SLOW_RESPONSE / 2, "{:?}", start.elapsed());
+    for slow_download in slow_downloads {
+        assert_eq!(slow_download.await.unwrap().validation, Validation::Pdf);
+    }
+}
+
+#[tokio::test]
+async fn stalled_download_times_out() {
+    let server = start_server().await;
+    let (downloader, root_path) = create_downloader_with("stalled", &["--read-timeout-secs", "1"]);
+    let report = create_report(
+        format!("{}/stalled.pdf", server),
+        vec![format!("{}/report.pdf", server)],
+    );
+
+    let start = Instant::now();
+    let download = downloader.download(&root_path, report).await.unwrap();
+    assert!(
+        start.elapsed() < Duration::from_secs(5),
+        "{:?}",
+        start.elapsed()
+    );
+    assert_eq!(download.validation, Validation::Pdf);
+    assert_eq!(download.source_link, format!("{}/report.pdf", server));
+}
+
+#[tokio::test]
+async fn rerun_keeps_the_source_of_existing_files() {
+    let server = start_server().await;
+    let (downloader, root_path) = create_downloader("rerun");
+    let report = create_report(
+        format!("{}/missing.pdf", server),
+        vec![format!("{}/report.pdf", server)],
+    );
+    let file_path = report.get_file_path(&root_path);
+    let download = downloader
+        .download(&root_path, report.clone())
+        .await
+        .unwrap();
+    let manifest = RunManifest {
+        downloads: vec![download],
+        ..Default::default()
+    };
+    // Marks the file to see whether it is downloaded again, without changing the stored copy it
+    // is linked to
+    std::fs::remove_file(&file_path).unwrap();
+    std::fs::write(&file_path, b"from the first execution").unwrap();
+
+    let mut downloader = open_downloader(root_path.parent().unwrap(), &[]);
+    downloader.set_previous_run(&root_path, &manifest);
+    let download = downloader
+        .download(&root_path, report.clone())
+        .await
+        .unwrap();
+    assert_eq!(download.source_link, format!("{}/report.pdf", server));
+    assert_eq!(
+        std::fs::read(&file_path).unwrap(),
+        b"from the first execution"
+    );
+
+    // Without the manifest the source of the file is unknown, so it is downloaded again
+    let downloader = open_downloader(root_path.parent().unwrap(), &[]);
+    let download = downloader.download(&root_path, report).await.unwrap();
+    assert_eq!(download.source_link, format!("{}/report.pdf", server));
+    assert_eq!(std::fs::read(&file_path).unwrap(), create_pdf());
+}
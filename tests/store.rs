@@ -0,0 +1,86 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::thread;
+
+use annual_report_database::store::{sha256_file, DocumentStore};
+
+fn create_directory(name: &str) -> PathBuf {
+    let directory = std::env::temp_dir().join(format!("annual_report_database_store_{}", name));
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(directory.join("run")).unwrap();
+    directory
+}
+
+#[cfg(unix)]
+fn get_inode(path: &std::path::Path) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    fs::metadata(path).unwrap().ino()
+}
+
+#[test]
+fn new_content_is_stored_and_linked() {
+    let directory = create_directory("new");
+    let store = DocumentStore::new(&directory.join("store"));
+    let file_path = directory.join("run").join("AR-EN.pdf");
+    fs::write(&file_path, b"report").unwrap();
+
+    let (sha256, is_new) = store.insert(&file_path).unwrap();
+    assert!(is_new);
+    assert_eq!(sha256, sha256_file(&file_path).unwrap());
+    assert!(store.contains(&sha256));
+    assert_eq!(fs::read(&file_path).unwrap(), b"report");
+    #[cfg(unix)]
+    assert_eq!(get_inode(&file_path), get_inode(&store.get_path(&sha256)));
+}
+
+#[test]
+fn known_content_is_linked_to_the_stored_copy() {
+    let directory = create_directory("known");
+    let store = DocumentStore::new(&directory.join("store"));
+    let first = directory.join("run").join("AR-EN.pdf");
+    let second = directory.join("run").join("AR-DE.pdf");
+    fs::write(&first, b"report").unwrap();
+    fs::write(&second, b"report").unwrap();
+
+    let (sha256, _) = store.insert(&first).unwrap();
+    assert_eq!(store.insert(&second).unwrap(), (sha256.clone(), false));
+    // Inserting a file that is already linked to the store changes nothing
+    assert_eq!(store.insert(&first).unwrap(), (sha256.clone(), false));
+    assert_eq!(fs::read(&second).unwrap(), b"report");
+    #[cfg(unix)]
+    assert_eq!(get_inode(&first), get_inode(&second));
+}
+
+#[test]
+fn identical_content_can_be_inserted_concurrently() {
+    let directory = create_directory("concurrent");
+    let store = Arc::new(DocumentStore::new(&directory.join("store")));
+    let paths: Vec<PathBuf> = (0..8)
+        .map(|i| directory.join("run").join(format!("{}.pdf", i)))
+        .collect();
+    for path in &paths {
+        fs::write(path, b"the same report").unwrap();
+    }
+
+    let handles: Vec<_> = paths
+        .iter()
+        .cloned()
+        .map(|path| {
+            let store = store.clone();
+            thread::spawn(move || store.insert(&path).unwrap())
+        })
+        .collect();
+    let results: Vec<(String, bool)> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+
+    assert_eq!(results.iter().filter(|(_, is_new)| *is_new).count(), 1);
+    for path in &paths {
+        assert_eq!(fs::read(path).unwrap(), b"the same report");
+    }
+    // No temporary files are left in the store
+    let sha256 = &results[0].0;
+    let stored: Vec<_> = fs::read_dir(store.get_path(sha256).parent().unwrap())
+        .unwrap()
+        .collect();
+    assert_eq!(stored.len(), 1);
+}